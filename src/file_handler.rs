@@ -1,9 +1,8 @@
 use std::{
     mem::size_of,
     path::PathBuf,
-    io::{Write, Read, Error, Seek},
+    io::{Write, Read, Error, Seek, SeekFrom},
     ptr,
-    io::ErrorKind,
     fs::File
 };
 use serde::{Serialize, Deserialize};
@@ -12,6 +11,11 @@ use crate::triangle::Triangle;
 
 pub const BLOCK_SIZE: usize = 1000*size_of::<Triangle>();
 
+/* bytes of whole records that fit in one block */
+fn page_len(record_size: usize) -> usize {
+    return BLOCK_SIZE - BLOCK_SIZE%record_size.max(1);
+}
+
 pub struct FileHandler {
     /* File */
     path: PathBuf,
@@ -20,6 +24,7 @@ pub struct FileHandler {
 
     /* data Block */
     page: Vec<u8>,
+    held: Option<Vec<u8>>,
     disk_ops: usize
 }
 
@@ -38,6 +43,7 @@ impl FileHandler {
                     file,
                     end_of_file: false,
                     page: Vec::with_capacity(BLOCK_SIZE),
+                    held: None,
                     disk_ops: 0
                 })
             }
@@ -45,30 +51,43 @@ impl FileHandler {
         }
     }
 
+    /* opens an already existing file without truncating it */
+    pub fn open_existing(path: PathBuf) -> Result<Self, Error> {
+        let file = File::options().read(true).open(path.clone())?;
+        Ok(Self {
+            path,
+            file,
+            end_of_file: false,
+            page: Vec::with_capacity(BLOCK_SIZE),
+            held: None,
+            disk_ops: 0
+        })
+    }
+
     pub fn view<T>(&mut self) -> Result<T, bincode::Error>
     where T: for<'a> Deserialize<'a> {
-        if self.page.len() == 0 { self.read_page()?; }
+        if self.page.is_empty() { self.read_page(size_of::<T>())?; }
         let page = &self.page[0..size_of::<T>()];
-        let obj: T = bincode::deserialize::<T>(&page)?;
+        let obj: T = bincode::deserialize::<T>(page)?;
         
-        if self.page.len() == 0 { self.read_page()?; }
+        if self.page.is_empty() { self.read_page(size_of::<T>())?; }
         Ok(obj)
     }
     pub fn read<T>(&mut self) -> Result<T, bincode::Error> 
     where T: for<'a> Deserialize<'a> {
-        if self.page.len() == 0 { self.read_page()?; }
+        if self.page.is_empty() { self.read_page(size_of::<T>())?; }
         
-        if self.page.len() < size_of::<T>() { return Err(Error::new(ErrorKind::Other, "page empty").into()); }
+        if self.page.len() < size_of::<T>() { return Err(Error::other("page empty").into()); }
         let mut page = self.page.split_off(size_of::<T>());
         unsafe {ptr::swap(&mut page, &mut self.page);}
         let obj: T = bincode::deserialize::<T>(&page)?;
 
-        if self.page.len() == 0 { self.read_page()?; }
+        if self.page.is_empty() { self.read_page(size_of::<T>())?; }
         Ok(obj)
     }
     pub fn write<T>(&mut self, obj: T) -> Result<(), bincode::Error> 
     where T: Serialize {
-        if self.page.len() == BLOCK_SIZE { self.write_page()?; }
+        if self.page.len() == page_len(size_of::<T>()) { self.write_page()?; }
 
         let mut page = bincode::serialize(&obj)?;
         self.page.append(&mut page);
@@ -87,20 +106,54 @@ impl FileHandler {
     }
     pub fn rewind(&mut self) -> Result<(), Error> {
         self.end_of_file = false;
+        self.held = None;
         self.file.rewind()?;
         self.page.clear();
         Ok(())
     }
+    /* saves the page without moving, so the file can be read by another handler */
+    pub fn sync(&mut self) -> Result<(), Error> {
+        self.write_page()
+    }
+    /* moves to pos while writing, data still in the page is read without touching the disk */
+    pub fn seek(&mut self, pos: u64) -> Result<(), Error> {
+        let disk_len = self.file.metadata()?.len();
+        if !self.page.is_empty() && pos >= disk_len && (pos - disk_len) as usize <= self.page.len() {
+            let tail = self.page.split_off((pos - disk_len) as usize);
+            self.held = Some(std::mem::replace(&mut self.page, tail));
+            self.end_of_file = true;
+            return Ok(());
+        }
+        self.write_page()?;
+        self.end_of_file = false;
+        self.file.seek(SeekFrom::Start(pos))?;
+        Ok(())
+    }
+    /* cuts the file at len and continues writing from there */
+    pub fn truncate(&mut self, len: u64) -> Result<(), Error> {
+        self.end_of_file = false;
+        if let Some(page) = self.held.take() {
+            self.page = page;
+            return Ok(());
+        }
+        self.page.clear();
+        self.file.set_len(len)?;
+        self.file.seek(SeekFrom::Start(len))?;
+        Ok(())
+    }
     pub fn print_content<T>(&mut self) -> Result<(), Error> 
     where T: std::fmt::Display+for<'a> Deserialize<'a> {
 
         let disk_ops = self.disk_ops;
+        /* the tape may still be written to, it has to continue where it was */
+        let pos = self.file.stream_position()?;
+        let end_of_file = self.end_of_file;
 
         let page = self.page.clone();
         self.rewind()?;
         //self.page = page.clone();
 
-        while !self.eof() || self.page.len() != 0 {
+        while !self.eof() || !self.page.is_empty() {
             match self.read::<T>() {
                 Ok(obj) => println!("{obj}"),
                 Err(_) => break
@@ -114,26 +167,30 @@ impl FileHandler {
         }
 
         self.rewind()?;
+        self.file.seek(SeekFrom::Start(pos))?;
+        self.end_of_file = end_of_file;
         self.page = page.clone();
         self.disk_ops = disk_ops;
         Ok(())
     }
-    pub fn eof(&self) -> bool { return self.end_of_file && self.page.len() == 0; }
+    pub fn eof(&self) -> bool { return self.end_of_file && self.page.is_empty(); }
     /* getters */
     pub fn path(&self) -> PathBuf { return self.path.clone(); }
     pub fn disk_ops(&self) -> usize { return self.disk_ops; }
+    pub fn file_len(&self) -> Result<u64, Error> { return Ok(self.file.metadata()?.len()); }
 
 
     /* private methods */
 
-    fn read_page(&mut self) -> Result<(), Error> {
+    fn read_page(&mut self, record_size: usize) -> Result<(), Error> {
         if self.end_of_file { return Ok((/* no more blocks */)) }
-        if self.page.len() != 0 { return Err(Error::new(ErrorKind::Other, "block not empty")); }
+        if !self.page.is_empty() { return Err(Error::other("block not empty")); }
 
-        self.page = vec![0; BLOCK_SIZE];
+        let page_len = page_len(record_size);
+        self.page = vec![0; page_len];
         let bytes = self.file.read(&mut self.page)?;
         
-        if bytes < BLOCK_SIZE {
+        if bytes < page_len {
             self.page = self.page[0..bytes].to_vec();
             self.end_of_file = true;
         }
@@ -142,9 +199,9 @@ impl FileHandler {
         Ok(())
     }
     fn write_page(&mut self) -> Result<(),Error> {
-        if self.page.len() == 0 { return Ok(()) }
+        if self.page.is_empty() { return Ok(()) }
 
-        self.file.write(&self.page)?;
+        self.file.write_all(&self.page)?;
         self.page.clear();
 
         if self.end_of_file { self.end_of_file=false; }
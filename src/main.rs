@@ -1,9 +1,15 @@
 
-use std::{env, io::{self, Write}, fs};
+#![allow(clippy::needless_return)]
 
-use triangle::Triangle;
+use std::{env, io::{self, Write, BufRead}, fs, mem::size_of};
+
+use triangle::{Triangle, key::{Key, KeySpec, SortKey, IntegerKeys}};
 
 use natural_sort::tape::Tape;
+use natural_sort::cost::{self, Algorithm};
+use natural_sort::planner::MergePlan;
+use natural_sort::{Compare, SharedCompare, prefix::{self, Prefixed}, reduce::{Reduce, Count, Sum, KeepFirst}, presorted::{self, Violation}, join::{self, JoinKind}, setops::{self, SetOp}, adaptive::{self, Strategy}, sorted::SortExternal};
+use file_handler::BLOCK_SIZE;
 
 mod natural_sort;
 mod triangle;
@@ -17,43 +23,56 @@ fn main() -> Result<(), io::Error> {
     
     let mut tapes = Vec::new();
     tapes.push(Tape::new());
+
+    /* number of tapes used when sorting, None picks it automatically */
+    let mut tape_setting: Option<usize> = Some(2);
+    let args: Vec<String> = env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--tapes") {
+        match args.get(i+1).map(|arg| parse_tapes(arg)) {
+            Some(Ok(setting)) => tape_setting = setting,
+            Some(Err(err)) => println!("{err}"),
+            None => println!("--tapes needs a value")
+        }
+    }
+    /* threads generating the initial runs of a natural sort */
+    let mut threads: usize = 1;
     loop {
 
         let mut cmd = String::new();
         if io::stdin().read_line(&mut cmd)? == 0 { break; }
         match cmd.as_str().trim() {
             _ if cmd.starts_with("random") => {
-                let len;
                 cmd.pop(); //remove '\n'
                 let cmd: Vec<&str> = cmd.split(" ").collect();
-                if cmd.len() > 1 {
-                    len = cmd[1].parse().expect("Wrong argument");
+                let len: usize = if cmd.len() > 1 {
+                    cmd[1].parse().expect("Wrong argument")
                 } else {
                     let mut buf = String::new();
                     io::stdin().read_line(&mut buf)?;
                     buf.pop(); //remove '\n'
-                    len = buf.parse().expect("wrong argument");
-                }
+                    buf.parse().expect("wrong argument")
+                };
                 
+                let skewed = cmd.get(2) == Some(&"skewed");
                 // move to fn but only when everything else works
+                let mut rng = rand::thread_rng();
                 for _ in 0..len {
-                    let rand_record = rand::random::<Triangle>();
+                    let rand_record = if skewed { Triangle::skewed(&mut rng) } else { rand::random::<Triangle>() };
                     tapes[0].push(rand_record);
                 }
             }
 
             _ if cmd.starts_with("add") => {
-                let record: Triangle;
                 cmd.pop(); //remove '\n'
                 let cmd: Vec<&str> = cmd.split(" ").collect();
-                if cmd.len() > 1 {
-                    record = cmd[1].parse().expect("Wrong format");
+                let record: Triangle = if cmd.len() > 1 {
+                    cmd[1].parse().expect("Wrong format")
                 } else {
                     let mut buf = String::new();
                     io::stdin().read_line(&mut buf)?;
                     buf.pop(); //remove '\n'
-                    record = buf.parse().expect("wrong format");
-                }
+                    buf.parse().expect("wrong format")
+                };
                 tapes[0].push(record);
             } 
 
@@ -71,6 +90,7 @@ fn main() -> Result<(), io::Error> {
                 let data  = fs::read_to_string(path)?;
                 let records = data.split("\n");
                 for record in records {
+                    if record.trim().is_empty() { continue; }
                     let record: Triangle = record.parse().expect("Wrong format");
                     tapes[0].push(record);
                     records_loaded+=1;
@@ -81,28 +101,470 @@ fn main() -> Result<(), io::Error> {
             "print enable" => { display_after_run!(true); }
             "print disable" => { display_after_run!(false); }
 
-            "sort" => {
+            _ if cmd.starts_with("tapes") => {
+                if let Some(arg) = cmd.split_whitespace().nth(1) {
+                    match parse_tapes(arg) {
+                        Ok(setting) => tape_setting = setting,
+                        Err(err) => println!("{err}")
+                    }
+                }
+                match tape_setting {
+                    Some(n) => println!("tapes: {n}"),
+                    None => println!("tapes: auto")
+                }
+            }
+
+            _ if cmd.starts_with("sort") => {
+                let (mut args, order) = match parse_order(&cmd) {
+                    Ok(parsed) => parsed,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+                let stable = args.contains(&"--stable");
+                let prefix = args.contains(&"--prefix");
+                let dedup = args.contains(&"--dedup");
+                let dedup_exact = args.contains(&"--dedup-exact");
+                let external = args.contains(&"--external");
+                args.retain(|&arg| arg != "--stable" && arg != "--prefix" && arg != "--dedup" && arg != "--dedup-exact" && arg != "--external");
+                if stable && dedup_exact {
+                    println!("--dedup-exact orders equal keys by their contents, it cannot keep them in input order with --stable");
+                    continue;
+                }
+                let mut reducer = None;
+                if let Some(i) = args.iter().position(|&arg| arg == "--reduce") {
+                    match args.get(i+1) {
+                        Some(&arg) if ["count", "sum", "first"].contains(&arg) => reducer = Some(arg),
+                        Some(arg) => { println!("unknown reducer: {arg} (count/sum/first)"); continue; }
+                        None => { println!("--reduce needs a value"); continue; }
+                    }
+                    args.drain(i..i+2);
+                }
+                let (algorithm, setting) = match parse_algorithm(&mut args, tape_setting) {
+                    Ok(parsed) => parsed,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+
+                for tape in &mut tapes {
+                    tape.flush()?;
+                }
+                let (algorithm, n) = match algorithm {
+                    Some(algorithm) => match resolve_tapes(algorithm, setting, &mut tapes[0], &order) {
+                        Ok(n) => (algorithm, n),
+                        Err(err) => { println!("{err}"); continue; }
+                    },
+                    None => {
+                        let plan = order.with_cmp(|cmp| adaptive::plan(&mut tapes[0], cmp, setting, external))?;
+                        print!("{plan}");
+                        match plan.strategy() {
+                            Some(Strategy::External(algorithm, n)) => (algorithm, n),
+                            /* any algorithm, the sort sees that the input fits */
+                            Some(Strategy::InMemory) => (Algorithm::Natural, 2),
+                            None => continue
+                        }
+                    }
+                };
+                if let Some(keys) = &order.keys {
+                    println!("sorting by {keys}{}", if order.descending { ", reversed" } else { "" });
+                }
+                let mut count = Count::new();
+                let mut sum = Sum::new(|triangle: &Triangle| triangle.tri_area());
+                let mut reduce = match reducer {
+                    Some("count") => Reduce::Combine(&mut count),
+                    Some("sum") => Reduce::Combine(&mut sum),
+                    Some(_) => Reduce::Combine(&mut KeepFirst),
+                    None if dedup_exact => Reduce::DropIdentical,
+                    None if dedup => Reduce::DropDuplicates,
+                    None => Reduce::Keep
+                };
+                *natural_sort::FORCE_EXTERNAL.lock().unwrap() = external;
+                /* input that fits is sorted in memory like with one thread */
+                let parallel = threads > 1 && algorithm == Algorithm::Natural && !stable && !prefix && matches!(reduce, Reduce::Keep)
+                    && !natural_sort::fits_in_memory(&tapes[0]);
+                let sorted = if parallel {
+                    /* every thread holds one chunk, the reading thread one more */
+                    let chunk_len = natural_sort::memory_records::<Triangle>()/(threads + 1);
+                    println!("generating runs with {threads} threads, {chunk_len} records per chunk");
+                    order.with_cmp(|cmp| natural_sort::parallel::sort(&mut tapes, n, threads, chunk_len, cmp))
+                } else if prefix {
+                    sort_prefixed(algorithm, &mut tapes, n, stable, &order, &mut reduce)
+                } else {
+                    sort(algorithm, &mut tapes, n, stable, &order, &mut reduce)
+                };
+                *natural_sort::FORCE_EXTERNAL.lock().unwrap() = false;
+                match sorted {
+                    Ok(info) => print_end_info(&info, prefix),
+                    Err(err) => println!("{err}")
+                }
+                /* groups are read back from their tapes one page at a time */
+                if reducer == Some("count") {
+                    for group in count.groups.iter() {
+                        let group = group?;
+                        println!("{} count: {}", group.first, group.value);
+                    }
+                }
+                if reducer == Some("sum") {
+                    for group in sum.groups.iter() {
+                        let group = group?;
+                        println!("{} sum: {:.2}", group.first, group.value);
+                    }
+                }
+            }
+
+            _ if cmd.starts_with("topk") => {
+                let (args, order) = match parse_order(&cmd) {
+                    Ok(parsed) => parsed,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+                let k: usize = match args.first().map(|arg| arg.parse()) {
+                    Some(Ok(k)) => k,
+                    _ => { println!("usage: topk {{k}} [--desc] [by {{keys}}]"); continue; }
+                };
+                for tape in &mut tapes {
+                    tape.flush()?;
+                }
+                let n = match resolve_tapes(Algorithm::Planned, tape_setting, &mut tapes[0], &order) {
+                    Ok(n) => n,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+                let budget = natural_sort::memory_records::<Triangle>();
+                match order.with_cmp(|cmp| natural_sort::topk::top_k(&mut tapes, k, budget, n, cmp)) {
+                    Ok(info) => print_end_info(&info, false),
+                    Err(err) => println!("{err}")
+                }
+            }
+
+            _ if cmd.starts_with("radix") => {
+                let (args, order) = match parse_order(&cmd) {
+                    Ok(parsed) => parsed,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+                let digit_bits: u32 = match args.first().map(|arg| arg.parse()) {
+                    Some(Ok(bits)) => bits,
+                    None => natural_sort::radix::MAX_DIGIT_BITS,
+                    _ => { println!("usage: radix [digit bits] [--desc] [by {{keys}}]"); continue; }
+                };
+                let keys = match IntegerKeys::try_from(&order.key_spec()) {
+                    Ok(keys) => keys,
+                    Err(err) => { println!("{err}"); continue; }
+                };
                 for tape in &mut tapes {
                     tape.flush()?;
                 }
-                let info = natural_sort::sort(&mut tapes, 2).expect("Problem sorting");
+                match natural_sort::radix::sort(&mut tapes, &keys, digit_bits) {
+                    Ok(info) => print_end_info(&info, false),
+                    Err(err) => println!("{err}")
+                }
+            }
+
+            _ if cmd.starts_with("stream") => {
+                let (args, order) = match parse_order(&cmd) {
+                    Ok(parsed) => parsed,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+                let [input, output] = args[..] else {
+                    println!("usage: stream {{input file}} {{output file}} [--desc] [by {{keys}}]");
+                    continue;
+                };
+                /* auto needs the records on a tape to count runs */
+                let n = tape_setting.unwrap_or(2);
+                /* records are parsed while they are sorted, a wrong line leaves no output behind */
+                let mut wrong_line = None;
+                let mut read_error = None;
+                let records = io::BufReader::new(fs::File::open(input)?).lines()
+                    .enumerate()
+                    .map_while(|(i, line)| match line {
+                        Ok(line) => Some((i, line)),
+                        Err(err) => { read_error = Some(err); None }
+                    })
+                    .filter(|(_, line)| !line.trim().is_empty())
+                    .map_while(|(i, line)| match line.parse::<Triangle>() {
+                        Ok(record) => Some(record),
+                        Err(_) => { wrong_line = Some(i + 1); None }
+                    });
+                let sorted = order.with_cmp(|cmp| records.sort_external(Algorithm::Natural, n, cmp))?;
+                if let Some(err) = read_error {
+                    return Err(err);
+                }
+                if let Some(line) = wrong_line {
+                    println!("{input}:{line}: wrong format");
+                    continue;
+                }
+                print_end_info(sorted.info(), false);
+                let written = presorted::write_lines(sorted.map(Ok), output.as_ref())?;
+                println!("wrote {written} sorted records to {output}");
+            }
 
-                println!("end info:");
-                println!("number of phases: {}", info.number_of_phases);
+            _ if cmd.starts_with("merge") => {
+                let (files, order) = match parse_order(&cmd) {
+                    Ok(parsed) => parsed,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+                if files.is_empty() {
+                    println!("usage: merge {{file}} {{file}} ... [--desc] [by {{keys}}]");
+                    continue;
+                }
+                /* the given tape is replaced only when every file turns out to be sorted */
+                let mut merged = Tape::new();
+                match order.with_cmp(|cmp| presorted::merge_files(&files, &mut merged, cmp)) {
+                    Ok(info) => {
+                        merged.print();
+                        tapes[0] = merged;
+                        print_end_info(&info, false);
+                    }
+                    Err(err) => match err.get_ref().and_then(|err| err.downcast_ref::<Violation>()) {
+                        Some(violation) => println!("{} is not sorted: record {} {} comes after {}",
+                            files[violation.input], violation.position, violation.record, violation.previous),
+                        None => println!("{err}")
+                    }
+                }
+            }
+
+            _ if cmd.starts_with("join") => {
+                let (mut args, order) = match parse_order(&cmd) {
+                    Ok(parsed) => parsed,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+                let mut out = None;
+                if let Some(i) = args.iter().position(|&arg| arg == "--out") {
+                    match args.get(i+1) {
+                        Some(&path) => out = Some(path),
+                        None => { println!("--out needs a file"); continue; }
+                    }
+                    args.drain(i..i+2);
+                }
+                let kind = match args.get(2).map(|arg| arg.parse::<JoinKind>()) {
+                    Some(Ok(kind)) => kind,
+                    Some(Err(err)) => { println!("{err}"); continue; }
+                    None => JoinKind::Inner
+                };
+                if args.len() < 2 {
+                    println!("usage: join {{left file}} {{right file}} [inner/left/anti] [--out {{file}}] [--desc] [by {{keys}}]");
+                    continue;
+                }
+                let (mut left, mut right) = match (presorted::load(args[0].as_ref()), presorted::load(args[1].as_ref())) {
+                    (Ok(left), Ok(right)) => (vec![left], vec![right]),
+                    (Err(err), _) | (_, Err(err)) => { println!("{err}"); continue; }
+                };
+                let n = match resolve_tapes(Algorithm::Natural, tape_setting, &mut left[0], &order) {
+                    Ok(n) => n,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+                let mut output = Tape::new();
+                let info = match order.with_cmp(|cmp| join::join(&mut left, &mut right, &mut output, kind, cmp, Algorithm::Natural, n)) {
+                    Ok(info) => info,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+                println!();println!();
+                output.print();
+                if let Some(path) = out {
+                    match presorted::save(&mut output, path.as_ref()) {
+                        Ok(rows) => println!("saved {rows} rows to {path}"),
+                        Err(err) => println!("{err}")
+                    }
+                }
+                println!("{kind} join:");
+                print_input_sorts(&info.left_sort, &info.right_sort);
+                println!("matched pairs: {}", info.matched);
+                println!("left records without a match: {}", info.unmatched);
+                println!("largest group of equal keys: {}", info.largest_group);
                 println!("disk operations: {}", info.disk_ops);
-                println!("teoretical values: ");
-                println!("number of phases: {}", info.teor_number_of_phases);
-                println!("disk operations: {}", info.teor_disk_ops);
+            }
+
+            _ if cmd.split_whitespace().next().is_some_and(|word| word.parse::<SetOp>().is_ok()) => {
+                let mut args: Vec<&str> = cmd.split_whitespace().collect();
+                let Ok(op) = args.remove(0).parse::<SetOp>() else { continue; };
+                let vertices = args.contains(&"--vertices");
+                args.retain(|&arg| arg != "--vertices");
+                if args.len() != 2 {
+                    println!("usage: {op} {{left file}} {{right file}} [--vertices]");
+                    continue;
+                }
+                let (mut left, mut right) = match (presorted::load(args[0].as_ref()), presorted::load(args[1].as_ref())) {
+                    (Ok(left), Ok(right)) => (vec![left], vec![right]),
+                    (Err(err), _) | (_, Err(err)) => { println!("{err}"); continue; }
+                };
+                let n = match resolve_tapes(Algorithm::Natural, tape_setting, &mut left[0], &Order::default()) {
+                    Ok(n) => n,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+                /* equal areas are the same key unless whole vertices are compared */
+                let cmp: Compare<Triangle> = if vertices { &triangle::vertex_order } else { &natural_sort::natural_order };
+                let mut result = Tape::new();
+                let info = match setops::apply(op, &mut left, &mut right, &mut result, cmp, Algorithm::Natural, n) {
+                    Ok(info) => info,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+                println!();println!();
+                result.print();
+                tapes[0] = result;
+                println!("{op} by {}:", if vertices { "vertices" } else { "area" });
+                print_input_sorts(&info.left_sort, &info.right_sort);
+                println!("keys: {} left, {} right, {} in both", info.left_keys, info.right_keys, info.common_keys);
+                println!("records written: {}", info.written);
+                println!("duplicate records skipped: {}", info.duplicates);
+                println!("disk operations: {}", info.disk_ops);
+            }
+
+            _ if cmd.starts_with("threads") => {
+                if let Some(arg) = cmd.split_whitespace().nth(1) {
+                    match arg.parse() {
+                        Ok(0) | Err(_) => println!("wrong number of threads: {arg}"),
+                        Ok(n) => threads = n
+                    }
+                }
+                println!("threads: {threads}");
+            }
+
+            _ if cmd.starts_with("memory") => {
+                if let Some(arg) = cmd.split_whitespace().nth(1) {
+                    match arg.parse() {
+                        Ok(pages) => *natural_sort::MEMORY_PAGES.lock().unwrap() = pages,
+                        Err(_) => println!("wrong number of pages: {arg}")
+                    }
+                }
+                let pages = *natural_sort::MEMORY_PAGES.lock().unwrap();
+                println!("memory: {pages} pages ({} records)", natural_sort::memory_records::<Triangle>());
+            }
+
+            _ if cmd.starts_with("compare") => {
+                let stable = cmd.split_whitespace().any(|arg| arg == "--stable");
+                let order = Order{ keys: None, descending: cmd.split_whitespace().any(|arg| arg == "--desc") };
+                let setting = match cmd.split_whitespace().skip(1).find(|&arg| arg != "--stable" && arg != "--desc") {
+                    Some(arg) => match parse_tapes(arg) {
+                        Ok(setting) => setting,
+                        Err(err) => { println!("{err}"); continue; }
+                    },
+                    None => tape_setting
+                };
+                for tape in &mut tapes {
+                    tape.flush()?;
+                }
+                let mut results = Vec::new();
+                /* the algorithms are compared on tapes, not on how well everything fits in memory */
+                *natural_sort::FORCE_EXTERNAL.lock().unwrap() = true;
+                for algorithm in [Algorithm::Natural, Algorithm::Balanced, Algorithm::Cascade, Algorithm::Oscillating, Algorithm::Planned, Algorithm::Distribution] {
+                    let n = match resolve_tapes(algorithm, setting, &mut tapes[0], &order) {
+                        Ok(n) => n,
+                        Err(err) => { println!("{algorithm}: {err}"); continue; }
+                    };
+                    let mut copy = vec![tapes[0].iter().collect::<Result<Tape<Triangle>, _>>()?];
+                    tapes[0].flush()?;
+                    let info = sort(algorithm, &mut copy, n, stable, &order, &mut Reduce::Keep).expect("Problem sorting");
+                    results.push((algorithm, info));
+                }
+                *natural_sort::FORCE_EXTERNAL.lock().unwrap() = false;
+
+                println!("{:<12} {:>8} {:>8} {:>10} {:>12} {:>8} {:>8}", "algorithm", "phases", "teor", "disk ops", "teor", "err ph", "err ops");
+                for (algorithm, info) in results {
+                    println!("{:<12} {:>8} {:>8} {:>10} {:>12.1} {:>8.2} {:>8.2}", algorithm.to_string(),
+                        info.number_of_phases, info.teor_number_of_phases,
+                        info.disk_ops, info.teor_disk_ops,
+                        info.phases_error(), info.disk_ops_error());
+                }
+            }
+
+            _ if cmd.starts_with("explain") => {
+                let Some(sort_cmd) = cmd.strip_prefix("explain").map(str::trim).filter(|rest| rest.starts_with("sort")) else {
+                    println!("usage: explain sort [adaptive/{{algorithm}}] [{{n}}] [--external] [--desc] [by {{keys}}]");
+                    continue;
+                };
+                let (mut args, order) = match parse_order(sort_cmd) {
+                    Ok(parsed) => parsed,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+                let external = args.contains(&"--external");
+                args.retain(|arg| !arg.starts_with("--") || *arg == "--tapes");
+                let (algorithm, setting) = match parse_algorithm(&mut args, tape_setting) {
+                    Ok(parsed) => parsed,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+                for tape in &mut tapes {
+                    tape.flush()?;
+                }
+                let plan = order.with_cmp(|cmp| adaptive::plan(&mut tapes[0], cmp, setting, external))?;
+                print!("{plan}");
+                if let Some(algorithm) = algorithm {
+                    if plan.candidates.iter().any(|candidate| candidate.0 == Strategy::InMemory) {
+                        println!("sort {algorithm} sorts in memory, the input fits (--external uses tapes)");
+                        continue;
+                    }
+                    let n = match resolve_tapes(algorithm, setting, &mut tapes[0], &order) {
+                        Ok(n) => n,
+                        Err(err) => { println!("{err}"); continue; }
+                    };
+                    let teor = cost::estimate(algorithm, n, plan.runs, plan.records, plan.b);
+                    println!("sort {algorithm} with {n} tapes: number of phases: {} disk operations: {:.1}", teor.phases, teor.disk_ops);
+                }
+            }
+
+            _ if cmd.starts_with("plan") => {
+                let setting = match cmd.split_whitespace().nth(1) {
+                    Some(arg) => match parse_tapes(arg) {
+                        Ok(setting) => setting,
+                        Err(err) => { println!("{err}"); continue; }
+                    },
+                    None => tape_setting
+                };
+                for tape in &mut tapes {
+                    tape.flush()?;
+                }
+                let fan_in = match resolve_tapes(Algorithm::Planned, setting, &mut tapes[0], &Order::default()) {
+                    Ok(n) => n,
+                    Err(err) => { println!("{err}"); continue; }
+                };
+                let run_len = natural_sort::planner::detect_runs(&mut tapes[0], &natural_sort::natural_order)?;
+                print!("{}", MergePlan::new(run_len, fan_in));
+            }
+
+            _ if cmd.starts_with("cost") => {
+                let args: Vec<usize> = cmd.split_whitespace().skip(1)
+                    .map(|arg| arg.parse().expect("Wrong argument"))
+                    .collect();
+                if args.len() != 3 {
+                    println!("usage: cost {{records}} {{runs}} {{tapes}}");
+                    continue;
+                }
+                let b = (BLOCK_SIZE/size_of::<Triangle>()) as f32;
+                for algorithm in Algorithm::ALL {
+                    let teor = cost::estimate(algorithm, args[2], args[1], args[0], b);
+                    println!("{algorithm}: number of phases: {} disk operations: {}", teor.phases, teor.disk_ops);
+                }
             }
 
             "help" => {
-                println!("random {{x}} - adds x amount of random records to starting tape");
+                println!("random {{x}} [skewed] - adds x amount of random records to starting tape, skewed ones are mostly tiny");
                 println!("add {{record}} - adds record");
                 println!("load {{file}} - loads records form file");
                 
                 println!("print enable/disable - enables/disables printing additional info when sorting");
 
                 println!("sort - sorts given tape");
+                println!("sort natural/balanced/cascade/oscillating/planned/distribution {{n}} - sorts given tape using n (per group) tapes");
+                println!("sort adaptive - picks the strategy with the lowest expected cost and prints the plan first");
+                println!("explain sort ... - prints the plan of sort adaptive without sorting, and the cost of the given algorithm");
+                println!("sort ... --tapes {{n}}/auto - same as above");
+                println!("sort ... --stable - keeps input order of records with equal keys");
+                println!("sort ... --desc - sorts in descending order");
+                println!("sort ... --dedup - drops records with equal keys, --dedup-exact only identical records");
+                println!("sort ... --reduce count/sum/first - combines records with equal keys into the first one of them");
+                println!("sort ... --prefix - stores a normalized key prefix with every record and compares it first");
+                println!("sort ... --external - uses tapes even when the input fits in memory");
+                println!("sort ... by {{key}} asc/desc, ... - sorts by area/perimeter/longest-side/centroid-x/centroid-y/min-vertex/bbox-area");
+                println!("tapes {{n}}/auto - sets number of tapes used when sorting (--tapes on the command line)");
+                println!("plan {{n}} - prints merge plan of given tape for fan-in n (sort planned {{n}} executes it)");
+                println!("compare {{n}} - sorts a copy of given tape with every algorithm and prints a comparison");
+                println!("compare {{n}} --stable - same as above, every algorithm sorts stably");
+                println!("compare {{n}} --desc - same as above, sorts in descending order");
+                println!("cost {{records}} {{runs}} {{tapes}} - prints theoretical cost of every algorithm");
+                println!("topk {{k}} - keeps the first k records of the sorted order, takes --desc and by {{keys}} like sort");
+                println!("radix [digit bits] - LSD radix sort by the integer keys, the widest digits by default, takes --desc and by {{keys}} like sort");
+                println!("stream {{input file}} {{output file}} - sorts a file into another one without touching given tape, takes --desc and by {{keys}} like sort");
+                println!("merge {{file}} {{file}} ... - merges files that are already sorted into given tape, takes --desc and by {{keys}} like sort");
+                println!("join {{left file}} {{right file}} inner/left/anti - sort-merge join on equal keys, takes --desc and by {{keys}} like sort");
+                println!("join ... --out {{file}} - also saves the joined rows to file");
+                println!("union/intersect/difference/symdiff {{left file}} {{right file}} - set operation on equal areas, the result replaces given tape");
+                println!("union ... --vertices - records are equal only with the same vertices");
+                println!("threads {{n}} - sets how many threads generate the initial runs of sort natural and merge its last run");
+                println!("memory {{pages}} - sets how many pages of records may be kept in memory");
 
                 println!("clear - flushes stdout");
                 println!("exit - exits program");
@@ -115,3 +577,148 @@ fn main() -> Result<(), io::Error> {
     }
     Ok(())
 }
+
+/* sort keys and direction of a sort, by area when there are no keys */
+#[derive(Default)]
+struct Order {
+    keys: Option<KeySpec>,
+    descending: bool
+}
+
+impl Order {
+    fn with_cmp<T>(&self, f: impl FnOnce(SharedCompare<Triangle>) -> T) -> T {
+        let cmp = |a: &Triangle, b: &Triangle| match &self.keys {
+            Some(keys) => keys.compare(a, b),
+            None => natural_sort::natural_order(a, b)
+        };
+        if self.descending {
+            return f(&natural_sort::reverse(&cmp));
+        }
+        return f(&cmp);
+    }
+    /* the same order as a list of keys, sorting by area is sorting by the area key */
+    fn key_spec(&self) -> KeySpec {
+        let mut keys = self.keys.clone().unwrap_or(KeySpec(vec![SortKey{ key: Key::Area, descending: false }]));
+        if self.descending {
+            for key in &mut keys.0 { key.descending = !key.descending; }
+        }
+        return keys;
+    }
+}
+
+fn sort(algorithm: Algorithm, tapes: &mut [Tape<Triangle>], n: usize, stable: bool, order: &Order, reduce: &mut Reduce<Triangle>) -> Result<natural_sort::SortInfo, io::Error> {
+    if stable {
+        return order.with_cmp(|cmp| natural_sort::sort_stable(algorithm, tapes, n, cmp, reduce));
+    }
+    if !matches!(reduce, Reduce::Keep) {
+        return order.with_cmp(|cmp| natural_sort::sort_reduced(algorithm, tapes, n, cmp, reduce));
+    }
+    if let (Some(key), false) = (order.keys.as_ref().and_then(KeySpec::single), order.descending) {
+        return natural_sort::sort_by_key(algorithm, tapes, n, |triangle| key.value(triangle));
+    }
+    return match algorithm {
+        Algorithm::Natural if order.keys.is_none() && !order.descending => natural_sort::sort(tapes, n),
+        Algorithm::Natural => order.with_cmp(|cmp| natural_sort::sort_by(tapes, n, cmp)),
+        _ => order.with_cmp(|cmp| natural_sort::sort_with(algorithm, tapes, n, cmp))
+    }
+}
+
+/* splits off the sort keys after "by" and the --desc flag, returns the remaining arguments */
+fn parse_order(cmd: &str) -> Result<(Vec<&str>, Order), String> {
+    let (cmd, keys) = match cmd.split_once(" by ") {
+        Some((cmd, keys)) => (cmd, Some(keys.parse::<KeySpec>()?)),
+        None => (cmd, None)
+    };
+    let mut args: Vec<&str> = cmd.split_whitespace().skip(1).collect();
+    let order = Order{ keys, descending: args.contains(&"--desc") };
+    args.retain(|&arg| arg != "--desc");
+    return Ok((args, order));
+}
+
+fn print_end_info(info: &natural_sort::SortInfo, prefix: bool) {
+    println!("end info:");
+    println!("initial runs: {} ascending, {} descending", info.ascending_runs, info.descending_runs);
+    if info.buckets != 0 {
+        println!("buckets: {}", info.buckets);
+    }
+    println!("number of phases: {}", info.number_of_phases);
+    println!("disk operations: {}", info.disk_ops);
+    println!("comparisons: {}", info.comparisons);
+    if info.in_memory {
+        println!("sorted in memory, the input fits in the memory budget (sort ... --external uses tapes anyway)");
+    }
+    if info.eliminated != 0 {
+        println!("eliminated records: {}", info.eliminated);
+    }
+    if prefix {
+        println!("decided by key prefix: {} (records compared: {})", info.prefix_hits, info.comparisons - info.prefix_hits);
+    }
+    println!("teoretical values: ");
+    println!("number of phases: {}", info.teor_number_of_phases);
+    println!("disk operations: {}", info.teor_disk_ops);
+    println!("relative error: ");
+    println!("number of phases: {:.2}", info.phases_error());
+    println!("disk operations: {:.2}", info.disk_ops_error());
+}
+
+/* inputs of a join or a set operation are sorted only when they are not sorted already */
+fn print_input_sorts(left: &Option<natural_sort::SortInfo>, right: &Option<natural_sort::SortInfo>) {
+    for (name, sorted) in [("left", left), ("right", right)] {
+        match sorted {
+            Some(sorted) => println!("{name} input sorted first: {} phases, {} disk operations", sorted.number_of_phases, sorted.disk_ops),
+            None => println!("{name} input already sorted")
+        }
+    }
+}
+
+/* keys are computed once per record and compared as bytes */
+fn sort_prefixed(algorithm: Algorithm, tapes: &mut [Tape<Triangle>], n: usize, stable: bool, order: &Order, reduce: &mut Reduce<Triangle>) -> Result<natural_sort::SortInfo, io::Error> {
+    let keys = order.key_spec();
+    return prefix::sort(tapes, &keys, &|a, b| keys.compare(a, b), reduce, |prefixed, cmp| {
+        if stable {
+            return natural_sort::sort_stable::<Prefixed<Triangle>>(algorithm, prefixed, n, cmp, &mut Reduce::Keep);
+        }
+        return natural_sort::sort_with::<Prefixed<Triangle>>(algorithm, prefixed, n, cmp);
+    });
+}
+
+/* algorithm and number of tapes of a sort: "{algorithm} {n}" and "--tapes {n}", None is the adaptive strategy */
+fn parse_algorithm(args: &mut Vec<&str>, mut setting: Option<usize>) -> Result<(Option<Algorithm>, Option<usize>), String> {
+    if let Some(i) = args.iter().position(|&arg| arg == "--tapes") {
+        setting = parse_tapes(args.get(i+1).ok_or("--tapes needs a value")?)?;
+        args.drain(i..i+2);
+    }
+    let algorithm = match args.first() {
+        Some(&"adaptive") => None,
+        Some(arg) => Some(arg.parse()?),
+        None => Some(Algorithm::Natural)
+    };
+    if let Some(arg) = args.get(1) {
+        setting = parse_tapes(arg)?;
+    }
+    return Ok((algorithm, setting));
+}
+
+/* number of tapes or "auto" */
+fn parse_tapes(arg: &str) -> Result<Option<usize>, String> {
+    if arg == "auto" { return Ok(None); }
+    return match arg.parse() {
+        Ok(n) => Ok(Some(n)),
+        Err(_) => Err(format!("wrong number of tapes: {arg}"))
+    }
+}
+
+fn resolve_tapes(algorithm: Algorithm, setting: Option<usize>, tape: &mut Tape<Triangle>, order: &Order) -> Result<usize, String> {
+    let n = match setting {
+        Some(n) => n,
+        None => {
+            let runs = order.with_cmp(|cmp| natural_sort::estimate_runs(tape, cmp)).map_err(|err| err.to_string())?;
+            let b = (BLOCK_SIZE/size_of::<Triangle>()) as f32;
+            let n = cost::auto_tapes(algorithm, runs, tape.record_count(), b);
+            println!("{algorithm}: ~{runs} initial runs, using {n} tapes");
+            n
+        }
+    };
+    algorithm.check_tapes(n)?;
+    return Ok(n);
+}
@@ -0,0 +1,89 @@
+use std::{
+    io::Error,
+    fmt::{self, Display},
+    str::FromStr,
+    mem::size_of
+};
+use rand::distributions::{Distribution, Standard};
+use serde::{Serialize, Deserialize};
+
+use crate::file_handler::BLOCK_SIZE;
+
+use super::{tape::Tape, cost::{self, Algorithm, Cost}, Compare, memory_records, estimate_runs};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Strategy {
+    InMemory,
+    /* algorithm with n tapes */
+    External(Algorithm, usize)
+}
+
+impl Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Strategy::InMemory => write!(f, "in memory"),
+            Strategy::External(algorithm, n) => write!(f, "{algorithm} with {n} tapes")
+        }
+    }
+}
+
+/* every strategy that can sort the input with its expected cost, the cheapest one first */
+pub struct Plan {
+    pub records: usize,
+    /* estimated from the first page */
+    pub runs: usize,
+    /* records per page and records that fit in memory */
+    pub b: f32,
+    pub budget: usize,
+    /* None when every algorithm picks its own number of tapes */
+    pub tapes: Option<usize>,
+    pub candidates: Vec<(Strategy, Cost)>
+}
+
+impl Plan {
+    /* input that fits in memory is sorted there by every sort, so sorting in memory comes first then, whatever the cost;
+       the rest is ranked by disk operations, then by phases; algorithms that only have a cost model are left out,
+       external leaves out sorting in memory */
+    pub fn new(records: usize, runs: usize, b: f32, budget: usize, tapes: Option<usize>, external: bool) -> Self {
+        let mut candidates = Vec::new();
+        for algorithm in Algorithm::ALL {
+            if algorithm == Algorithm::Polyphase { continue; }
+            let n = tapes.unwrap_or_else(|| cost::auto_tapes(algorithm, runs, records, b));
+            if algorithm.check_tapes(n).is_err() { continue; }
+            candidates.push((Strategy::External(algorithm, n), cost::estimate(algorithm, n, runs, records, b)));
+        }
+        candidates.sort_by(|a, b| a.1.disk_ops.total_cmp(&b.1.disk_ops).then(a.1.phases.total_cmp(&b.1.phases)));
+        if records <= budget && !external {
+            candidates.insert(0, (Strategy::InMemory, cost::in_memory(records, b)));
+        }
+        return Self { records, runs, b, budget, tapes, candidates };
+    }
+
+    pub fn strategy(&self) -> Option<Strategy> {
+        return self.candidates.first().map(|candidate| candidate.0);
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tapes = self.tapes.map_or("auto".to_string(), |n| n.to_string());
+        writeln!(f, "input: {} records, ~{} runs, {} records per page, {} records fit in memory, tapes: {tapes}", self.records, self.runs, self.b, self.budget)?;
+        writeln!(f, "  {:<28} {:>8} {:>12}", "strategy", "phases", "disk ops")?;
+        for (i, (strategy, cost)) in self.candidates.iter().enumerate() {
+            writeln!(f, "{} {:<28} {:>8} {:>12.1}", if i == 0 { "*" } else { " " }, strategy.to_string(), cost.phases, cost.disk_ops)?;
+        }
+        match self.strategy() {
+            Some(strategy) => writeln!(f, "chosen: {strategy}"),
+            None => writeln!(f, "no strategy can sort with {tapes} tapes")
+        }
+    }
+}
+
+/* plan for sorting tape by cmp, the tape is rewound afterwards */
+pub fn plan<R>(tape: &mut Tape<R>, cmp: Compare<R>, tapes: Option<usize>, external: bool) -> Result<Plan, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let records = tape.record_count();
+    let runs = estimate_runs(tape, cmp)?;
+    let b = (BLOCK_SIZE/size_of::<R>()) as f32;
+    return Ok(Plan::new(records, runs, b, memory_records::<R>(), tapes, external));
+}
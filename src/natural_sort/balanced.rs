@@ -0,0 +1,79 @@
+use std::{
+    io::Error,
+    fmt::Display,
+    str::FromStr,
+    mem::{size_of, swap}
+};
+use rand::distributions::{Distribution, Standard};
+use serde::{Serialize, Deserialize};
+use cute::c;
+
+use crate::file_handler::BLOCK_SIZE;
+
+use super::{tape::Tape, cost, Compare, SortInfo, DISPLAY_AFTER_RUN, distribute, merge, merge_last, clear_tapes, print_info};
+use super::reduce::Reduce;
+
+/* balanced merge: runs ping-pong between two groups of n tapes, no separate distribution phase */
+pub fn sort<R>(target: &mut [Tape<R>], n: usize, cmp: Compare<R>, reduce: &mut Reduce<R>) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let mut disk_ops = 0;
+    let eliminated;
+    let mut number_of_phases = 0;
+    target[0].print();
+
+    let mut source: Vec<Tape<R>> = c![Tape::new(), for _i in 0..n];
+    let mut destination: Vec<Tape<R>> = c![Tape::new(), for _i in 0..n];
+
+    let runs = distribute(&mut target[0], &mut source, cmp);
+    for tape in target.iter_mut() { disk_ops+=tape.disk_ops() }
+    clear_tapes(target);
+
+    let display = DISPLAY_AFTER_RUN.lock().unwrap();
+    loop {
+        if *display {
+            print_info(&mut source);
+            println!();println!();
+        }
+
+        /* the last phase merges straight onto the target tape */
+        let left: usize = source.iter().map(|tape| tape.run_len.len()).sum();
+        if left <= n {
+            eliminated = merge_last(&mut source, &mut target[0], cmp, reduce);
+            for tape in source.iter_mut() { disk_ops+=tape.disk_ops() }
+            for tape in target.iter_mut() { disk_ops+=tape.disk_ops() }
+            number_of_phases+=1;
+            break;
+        }
+
+        merge(&mut source, &mut destination, cmp);
+        for tape in source.iter_mut() { disk_ops+=tape.disk_ops() }
+        clear_tapes(&mut source);
+        swap(&mut source, &mut destination);
+        number_of_phases+=1;
+    }
+    if *display {
+        print_info(target);
+    }
+    println!();println!();
+    target[0].print();
+
+    let b: f32 = (BLOCK_SIZE/size_of::<R>()) as f32;
+    let teor = cost::balanced(n, runs.total(), runs.records, b);
+    return Ok(SortInfo {
+        number_of_phases,
+        disk_ops,
+
+        teor_number_of_phases: teor.phases,
+        teor_disk_ops: teor.disk_ops,
+
+        ascending_runs: runs.ascending,
+        descending_runs: runs.descending,
+        buckets: 0,
+
+        comparisons: 0,
+        prefix_hits: 0,
+        eliminated,
+
+        in_memory: false
+    })
+}
@@ -0,0 +1,139 @@
+use std::{
+    io::Error,
+    fmt::Display,
+    str::FromStr,
+    mem::size_of
+};
+use rand::distributions::{Distribution, Standard};
+use serde::{Serialize, Deserialize};
+use cute::c;
+
+use crate::file_handler::BLOCK_SIZE;
+
+use super::{tape::Tape, cost, Compare, SortInfo, DISPLAY_AFTER_RUN, distribute_with, merge_run, merge_run_reduce, clear_tapes, print_info};
+use super::reduce::Reduce;
+
+/* cascade merge: n tapes, every phase does a (n-1)-way, (n-2)-way, ..., 2-way merge */
+pub fn sort<R>(target: &mut [Tape<R>], n: usize, cmp: Compare<R>, reduce: &mut Reduce<R>) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let k = n.max(3) - 1;
+    let mut disk_ops = 0;
+    let eliminated;
+    let mut number_of_phases = 0;
+    target[0].print();
+
+    let mut tapes: Vec<Tape<R>> = c![Tape::new(), for _i in 0..k+1];
+
+    /* runs go to the tape that is furthest from the current perfect distribution */
+    let mut level = vec![0; k];
+    level[0] = 1;
+    let mut dummies = level.clone();
+    let initial_runs = distribute_with(&mut target[0], &mut tapes[0..k], cmp, || {
+        if dummies.iter().all(|&d| d == 0) {
+            let next = cost::next_cascade_level(&level);
+            for i in 0..k { dummies[i] += next[i] - level[i]; }
+            level = next;
+        }
+        let mut i = 0;
+        for j in 1..k {
+            if dummies[j] > dummies[i] { i = j; }
+        }
+        dummies[i] -= 1;
+        i
+    });
+    /* dummy runs are empty, so they can be read first */
+    for i in 0..k {
+        tapes[i].run_len.insert_empty_front(dummies[i]);
+    }
+    for tape in target.iter_mut() { disk_ops+=tape.disk_ops() }
+    clear_tapes(target);
+
+    let display = DISPLAY_AFTER_RUN.lock().unwrap();
+    let mut next_run = vec![0; k+1];
+    loop {
+        if *display {
+            print_info(&mut tapes);
+            println!();println!();
+        }
+        let mut inputs: Vec<usize> = (0..k+1).filter(|&i| runs_left(&tapes, &next_run, i) != 0).collect();
+        let remaining: usize = inputs.iter().map(|&i| runs_left(&tapes, &next_run, i)).sum();
+
+        /* one run left on every tape: the last merge goes straight onto the target tape */
+        if remaining == inputs.len() {
+            let (mut runs, _) = front_runs(&mut tapes, &inputs, &mut next_run, None);
+            eliminated = merge_run_reduce(&mut runs, &mut target[0], cmp, reduce);
+            target[0].flush()?;
+            for tape in tapes.iter_mut() { disk_ops+=tape.disk_ops() }
+            for tape in target.iter_mut() { disk_ops+=tape.disk_ops() }
+            number_of_phases+=1;
+            break;
+        }
+
+        let mut output = (0..k+1).find(|&i| runs_left(&tapes, &next_run, i) == 0).unwrap();
+        while inputs.len() > 1 {
+            let m = inputs.iter().map(|&i| runs_left(&tapes, &next_run, i)).min().unwrap();
+            disk_ops+=tapes[output].disk_ops();
+            tapes[output].clear()?;
+            next_run[output] = 0;
+            for _ in 0..m {
+                let (mut runs, out) = front_runs(&mut tapes, &inputs, &mut next_run, Some(output));
+                merge_run(&mut runs, out.unwrap(), cmp);
+            }
+            tapes[output].flush()?;
+
+            /* the first tape to run out receives the next, narrower merge */
+            output = *inputs.iter().find(|&&i| runs_left(&tapes, &next_run, i) == 0).unwrap();
+            inputs.retain(|&i| runs_left(&tapes, &next_run, i) != 0);
+        }
+        number_of_phases+=1;
+    }
+    if *display {
+        print_info(target);
+    }
+    println!();println!();
+    target[0].print();
+
+    let b: f32 = (BLOCK_SIZE/size_of::<R>()) as f32;
+    let teor = cost::cascade(n, initial_runs.total(), initial_runs.records, b);
+    return Ok(SortInfo {
+        number_of_phases,
+        disk_ops,
+
+        teor_number_of_phases: teor.phases,
+        teor_disk_ops: teor.disk_ops,
+
+        ascending_runs: initial_runs.ascending,
+        descending_runs: initial_runs.descending,
+        buckets: 0,
+
+        comparisons: 0,
+        prefix_hits: 0,
+        eliminated,
+
+        in_memory: false
+    })
+}
+
+fn runs_left<R>(tapes: &[Tape<R>], next_run: &[usize], i: usize) -> usize
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    return tapes[i].run_len.len() - next_run[i];
+}
+
+/* the next run of every input tape, and the output tape if it is one of tapes */
+#[allow(clippy::type_complexity)]
+fn front_runs<'t, R>(tapes: &'t mut [Tape<R>], inputs: &[usize], next_run: &mut [usize], output: Option<usize>)
+-> (Vec<(&'t mut Tape<R>, usize)>, Option<&'t mut Tape<R>>)
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    let mut runs = Vec::new();
+    let mut out = None;
+    for (i, tape) in tapes.iter_mut().enumerate() {
+        if Some(i) == output {
+            out = Some(tape);
+        } else if inputs.contains(&i) {
+            let len = tape.run_len.at(next_run[i]);
+            next_run[i]+=1;
+            runs.push((tape, len));
+        }
+    }
+    return (runs, out);
+}
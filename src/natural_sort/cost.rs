@@ -0,0 +1,355 @@
+use std::{collections::VecDeque, fmt, str::FromStr};
+
+use super::{tape::MAX_OPEN_TAPES, distribution::SAMPLES, parallel, MEMORY_PAGES};
+
+/* expected cost of sorting `records` records split into `runs` initial runs, `b` records per page */
+#[derive(Clone, Copy)]
+pub struct Cost {
+    pub phases: f32,
+    pub disk_ops: f32
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Natural,
+    Balanced,
+    Polyphase,
+    Cascade,
+    Oscillating,
+    Planned,
+    Distribution
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 7] = [Algorithm::Natural, Algorithm::Balanced, Algorithm::Polyphase, Algorithm::Cascade, Algorithm::Oscillating, Algorithm::Planned, Algorithm::Distribution];
+
+    /* tapes open at once when sorting with n tapes, the target tape included */
+    pub fn open_tapes(&self, n: usize) -> usize {
+        return match self {
+            Algorithm::Balanced => 2*n + 1,
+            Algorithm::Natural | Algorithm::Distribution => n + 1,
+            Algorithm::Planned => n + 2,
+            Algorithm::Polyphase | Algorithm::Cascade | Algorithm::Oscillating => n.max(3) + 1,
+        }
+    }
+    pub fn check_tapes(&self, n: usize) -> Result<(), String> {
+        if n < 2 { return Err("at least 2 tapes are needed".to_string()); }
+        let open = self.open_tapes(n);
+        if open > MAX_OPEN_TAPES {
+            return Err(format!("{self} with {n} tapes keeps {open} files open, budget is {MAX_OPEN_TAPES}"));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Algorithm::Natural => "natural",
+            Algorithm::Balanced => "balanced",
+            Algorithm::Polyphase => "polyphase",
+            Algorithm::Cascade => "cascade",
+            Algorithm::Oscillating => "oscillating",
+            Algorithm::Planned => "planned",
+            Algorithm::Distribution => "distribution",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "natural" => Ok(Algorithm::Natural),
+            "balanced" => Ok(Algorithm::Balanced),
+            "polyphase" => Ok(Algorithm::Polyphase),
+            "cascade" => Ok(Algorithm::Cascade),
+            "oscillating" => Ok(Algorithm::Oscillating),
+            "planned" => Ok(Algorithm::Planned),
+            "distribution" => Ok(Algorithm::Distribution),
+            _ => Err(format!("unknown algorithm: {s}"))
+        }
+    }
+}
+
+pub fn estimate(algorithm: Algorithm, n: usize, runs: usize, records: usize, b: f32) -> Cost {
+    return match algorithm {
+        Algorithm::Natural => natural(n, runs, records, b),
+        Algorithm::Balanced => balanced(n, runs, records, b),
+        Algorithm::Polyphase => polyphase(n, runs, records, b),
+        Algorithm::Cascade => cascade(n, runs, records, b),
+        Algorithm::Oscillating => oscillating(n, runs, records, b),
+        Algorithm::Planned => balanced(n, runs, records, b),
+        Algorithm::Distribution => distribution(n, records, *MEMORY_PAGES.lock().unwrap() as f32*b, b),
+    }
+}
+
+/* the input is read into memory and the sorted records are written back */
+pub fn in_memory(records: usize, b: f32) -> Cost {
+    return Cost {
+        phases: 1_f32,
+        disk_ops: 2_f32*(records as f32)/b
+    }
+}
+
+/* distribute onto n tapes, merge back onto one: every phase reads and writes the file twice,
+   sorted input still takes one phase to find out it is sorted */
+pub fn natural(n: usize, runs: usize, records: usize, b: f32) -> Cost {
+    let phases = log_ceil(runs, n).max(1_f32);
+    return Cost {
+        phases,
+        disk_ops: 4_f32*(records as f32)*phases/b
+    }
+}
+
+/* threads sort chunks of the input at the same time and flush a partly filled page each, merging the runs of every thread
+   ends the first phase, the rest is a natural merge of what is left; with more threads the last merge reads a page
+   for every sample, every bound and the start of every run in each partition, writes the partitions and copies them
+   once more, runs past parallel::fan_in are merged in more passes */
+pub fn parallel(threads: usize, n: usize, chunks: usize, records: usize, b: f32) -> Cost {
+    let threads = threads.max(1);
+    let pages = records as f32/b;
+    let rest = log_ceil(chunks.div_ceil(threads), n);
+    let mut disk_ops = 4_f32*pages + threads as f32 + 4_f32*pages*rest;
+    if threads > 1 {
+        let partitions = threads.min(parallel::MAX_PARTITIONS);
+        let fan_in = parallel::fan_in(partitions);
+        let mut runs = if rest == 0_f32 { threads.min(chunks) } else { n };
+        loop {
+            let groups = runs.div_ceil(fan_in);
+            disk_ops += 2_f32*pages + (runs*partitions*(parallel::SAMPLES + 2) + 2*groups*partitions) as f32;
+            if groups <= 1 { break; }
+            /* the groups are written and read once more */
+            disk_ops += 2_f32*pages;
+            runs = groups;
+        }
+    }
+    return Cost {
+        phases: rest + 1_f32,
+        disk_ops
+    }
+}
+
+/* buckets are split into n until they fit in memory (budget records), every level reads and writes the file once
+   and reads a page for every sample, the last pass sorts the buckets in memory */
+pub fn distribution(n: usize, records: usize, budget: f32, b: f32) -> Cost {
+    let mut levels = 0;
+    let mut bucket = records as f32;
+    let mut samples = 0_f32;
+    while bucket > budget.max(1_f32) {
+        samples += (n.max(2)*SAMPLES) as f32*(n.max(2) as f32).powi(levels);
+        bucket /= n.max(2) as f32;
+        levels += 1;
+    }
+    let phases = (levels + 1) as f32;
+    return Cost {
+        phases,
+        disk_ops: 2_f32*(records as f32)*phases/b + samples
+    }
+}
+
+/* every pass reads and writes the file twice, through the buckets and back,
+   and writes and reads a partly filled page of every bucket that gets a record */
+pub fn radix(passes: usize, buckets: usize, records: usize, b: f32) -> Cost {
+    let phases = passes as f32;
+    return Cost {
+        phases,
+        disk_ops: (4_f32*(records as f32)/b + 2_f32*buckets.min(records) as f32)*phases
+    }
+}
+
+/* two groups of n tapes: one distribution pass, then every phase reads and writes the file once */
+pub fn balanced(n: usize, runs: usize, records: usize, b: f32) -> Cost {
+    let phases = log_ceil(runs, n);
+    return Cost {
+        phases,
+        disk_ops: 2_f32*(records as f32)*(phases + 1_f32)/b
+    }
+}
+
+/* n tapes, (n-1)-way merges, runs on a generalized fibonacci distribution */
+pub fn polyphase(n: usize, runs: usize, records: usize, b: f32) -> Cost {
+    let k = n.max(3) - 1;
+    let (level, distribution) = perfect_distribution(k, runs, next_polyphase_level);
+    let mut tapes = with_dummies(&distribution, runs);
+    tapes.push(VecDeque::new());
+
+    let mut moved = 0_f64;
+    let mut output = k;
+    while total_runs(&tapes) > 1 {
+        let inputs: Vec<usize> = (0..tapes.len()).filter(|&i| i != output).collect();
+        let m = inputs.iter().map(|&i| count(&tapes[i])).min().unwrap_or(0);
+        if m == 0 { break; }
+        moved += merge_runs(&mut tapes, &inputs, output, m);
+        output = inputs.into_iter().find(|&i| count(&tapes[i]) == 0).unwrap_or(output);
+    }
+    return simulated_cost(level, moved, runs, records, b);
+}
+
+/* n tapes, every phase does a (n-1)-way, (n-2)-way, ..., 2-way merge */
+pub fn cascade(n: usize, runs: usize, records: usize, b: f32) -> Cost {
+    let k = n.max(3) - 1;
+    let (level, distribution) = perfect_distribution(k, runs, next_cascade_level);
+    let mut tapes = with_dummies(&distribution, runs);
+    tapes.push(VecDeque::new());
+
+    let mut moved = 0_f64;
+    while total_runs(&tapes) > 1 {
+        let mut inputs: Vec<usize> = (0..tapes.len()).filter(|&i| count(&tapes[i]) != 0).collect();
+        let mut output = (0..tapes.len()).find(|&i| count(&tapes[i]) == 0).unwrap_or(k);
+        while inputs.len() > 1 {
+            let m = inputs.iter().map(|&i| count(&tapes[i])).min().unwrap_or(0);
+            moved += merge_runs(&mut tapes, &inputs, output, m);
+            output = inputs.iter().copied().find(|&i| count(&tapes[i]) == 0).unwrap_or(output);
+            inputs.retain(|&i| count(&tapes[i]) != 0);
+        }
+        /* the last input tape is renamed instead of copied */
+    }
+    return simulated_cost(level, moved, runs, records, b);
+}
+
+/* n tapes used as stacks, every (n-1) runs of one level are merged as soon as they exist */
+pub fn oscillating(n: usize, runs: usize, records: usize, b: f32) -> Cost {
+    let phases = log_ceil(runs, n.max(3) - 1);
+    return Cost {
+        phases,
+        disk_ops: 2_f32*(records as f32)*(phases + 1_f32)/b
+    }
+}
+
+/* first k records: with k inside the memory budget one pass through a heap,
+   otherwise sorted runs of budget records merged n at a time, every merged run cut to k records */
+pub fn top_k(n: usize, k: usize, budget: usize, records: usize, b: f32) -> Cost {
+    if k <= budget {
+        return Cost {
+            phases: 1_f32,
+            disk_ops: (records + k.min(records)) as f32/b
+        }
+    }
+    let mut runs = records.div_ceil(budget.max(1)).max(1);
+    let mut run = budget.max(1);
+    let mut phases = 1_f32;
+    let mut disk_ops = 2_f32*(records as f32)/b;
+    while runs > 1 {
+        let read = (runs*run).min(records);
+        runs = runs.div_ceil(n.max(2));
+        run = (run*n.max(2)).min(k);
+        disk_ops += (read + (runs*run).min(records)) as f32/b;
+        phases += 1_f32;
+    }
+    return Cost {
+        phases,
+        disk_ops
+    }
+}
+
+/* one k-way merge of inputs that are already sorted, every input costs at least one page */
+pub fn merge_sorted(input_len: &[usize], b: f32) -> Cost {
+    let records: usize = input_len.iter().sum();
+    return Cost {
+        phases: 1_f32,
+        disk_ops: input_len.iter().map(|&len| (len as f32/b).ceil().max(1_f32)).sum::<f32>() + records as f32/b
+    }
+}
+
+/* number of tapes with the lowest expected cost, every tape costs at least one page per phase */
+pub fn auto_tapes(algorithm: Algorithm, runs: usize, records: usize, b: f32) -> usize {
+    let mut best = (f32::MAX, 2);
+    for n in 2.. {
+        if algorithm.check_tapes(n).is_err() { break; }
+        let cost = estimate(algorithm, n, runs, records, b);
+        let disk_ops = cost.disk_ops + 2_f32*(algorithm.open_tapes(n) as f32)*cost.phases;
+        if disk_ops < best.0 { best = (disk_ops, n); }
+    }
+    return best.1;
+}
+
+/* relative error of a measured value against the theoretical one */
+pub fn relative_error(measured: f32, theoretical: f32) -> f32 {
+    return (measured - theoretical).abs()/theoretical.max(1_f32);
+}
+
+fn log_ceil(runs: usize, n: usize) -> f32 {
+    if runs <= 1 { return 0_f32; }
+    return ((runs as f32).ln()/(n.max(2) as f32).ln()).ceil();
+}
+
+fn simulated_cost(level: usize, moved: f64, runs: usize, records: usize, b: f32) -> Cost {
+    /* initial distribution + every run that takes part in a merge is read and written once */
+    let run_len = records as f64/runs.max(1) as f64;
+    let moved_records = moved*run_len;
+    return Cost {
+        phases: level as f32,
+        disk_ops: ((2_f64*records as f64 + 2_f64*moved_records)/b as f64) as f32
+    }
+}
+
+fn next_polyphase_level(a: &[usize]) -> Vec<usize> {
+    /* a is sorted descending */
+    let mut next: Vec<usize> = (1..a.len()).map(|i| a[0] + a[i]).collect();
+    next.push(a[0]);
+    return next;
+}
+
+pub(crate) fn next_cascade_level(a: &[usize]) -> Vec<usize> {
+    let k = a.len();
+    return (0..k).map(|j| a[0..k-j].iter().sum()).collect();
+}
+
+fn perfect_distribution(k: usize, runs: usize, next: fn(&[usize]) -> Vec<usize>) -> (usize, Vec<usize>) {
+    let mut level = 0;
+    let mut distribution = vec![0; k];
+    distribution[0] = 1;
+    while distribution.iter().sum::<usize>() < runs {
+        distribution = next(&distribution);
+        level += 1;
+    }
+    return (level, distribution);
+}
+
+/* tapes as (run weight, run count) groups, dummy runs of weight 0 go first */
+fn with_dummies(distribution: &[usize], runs: usize) -> Vec<VecDeque<(f64, usize)>> {
+    let total: usize = distribution.iter().sum();
+    let mut dummies = total - runs.max(1);
+    let mut tapes = Vec::new();
+    for (i, &real) in distribution.iter().enumerate() {
+        let left = distribution.len() - i;
+        let d = dummies.div_ceil(left).min(real);
+        dummies -= d;
+        let mut tape = VecDeque::new();
+        if d != 0 { tape.push_back((0_f64, d)); }
+        if real - d != 0 { tape.push_back((1_f64, real - d)); }
+        tapes.push(tape);
+    }
+    return tapes;
+}
+
+fn count(tape: &VecDeque<(f64, usize)>) -> usize {
+    return tape.iter().map(|(_, c)| c).sum();
+}
+
+fn total_runs(tapes: &[VecDeque<(f64, usize)>]) -> usize {
+    return tapes.iter().map(count).sum();
+}
+
+/* merges m runs from every input tape onto output, returns the weight that was read */
+fn merge_runs(tapes: &mut [VecDeque<(f64, usize)>], inputs: &[usize], output: usize, mut m: usize) -> f64 {
+    let mut moved = 0_f64;
+    while m > 0 {
+        let c = inputs.iter().map(|&i| tapes[i].front().map_or(0, |g| g.1)).min().unwrap_or(0).min(m);
+        if c == 0 { break; }
+        let mut weight = 0_f64;
+        for &i in inputs {
+            let front = tapes[i].front_mut().unwrap();
+            weight += front.0;
+            front.1 -= c;
+            if front.1 == 0 { tapes[i].pop_front(); }
+        }
+        tapes[output].push_back((weight, c));
+        moved += weight*c as f64;
+        m -= c;
+    }
+    return moved;
+}
@@ -0,0 +1,187 @@
+use std::{
+    io::Error,
+    cmp::Ordering,
+    fmt::Display,
+    str::FromStr,
+    mem::size_of,
+    slice
+};
+use rand::{Rng, distributions::{Distribution, Standard}};
+use serde::{Serialize, Deserialize};
+use cute::c;
+
+use crate::file_handler::BLOCK_SIZE;
+
+use super::{tape::{Tape, RecordSource, MAX_OPEN_TAPES}, cost, Compare, SortInfo, DISPLAY_AFTER_RUN, memory_records, fits_in_memory, natural, clear_tapes, print_info};
+use super::reduce::{Reduce, Writer};
+
+/* random records sampled for every bucket */
+pub const SAMPLES: usize = 8;
+
+/* distribution sort: keys sampled from target[0] cut it into up to n buckets in one pass,
+   buckets that fit in memory are sorted there and written in order, larger ones are split again,
+   a bucket that cannot be split is sorted by natural merge; reduce is applied while writing */
+pub fn sort<R>(target: &mut [Tape<R>], n: usize, cmp: Compare<R>, reduce: &mut Reduce<R>) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let records = target[0].record_count();
+    target[0].print();
+    let mut buckets = Buckets {
+        n: n.max(2),
+        budget: memory_records::<R>().max(1),
+        cmp,
+        disk_ops: 0,
+        sorted: 0,
+        levels: 0
+    };
+
+    /* target[0] is both the input and the output, so it is read completely before anything is written */
+    let mut in_memory = Vec::new();
+    let mut scattered = Vec::new();
+    /* --external scatters even a tape that fits in memory, an empty one has nothing to scatter */
+    let fits = fits_in_memory(&target[0]);
+    if fits || records == 0 {
+        for _i in 0..records { in_memory.push(target[0].next_record()); }
+    } else {
+        match buckets.scatter(&mut target[0], records)? {
+            Some(tapes) => scattered = tapes,
+            None => return natural(target, n, cmp, reduce)
+        }
+        buckets.levels = 1;
+    }
+    buckets.disk_ops+=target[0].disk_ops();
+    clear_tapes(target);
+
+    let display = DISPLAY_AFTER_RUN.lock().unwrap();
+    if *display && !scattered.is_empty() {
+        print_info(&mut scattered);
+        println!();println!();
+    }
+    drop(display);
+
+    let mut writer = Writer::new(reduce, cmp);
+    if scattered.is_empty() {
+        in_memory.sort_by(cmp);
+        for record in in_memory { writer.push(record, &mut target[0]); }
+        buckets.sorted+=1;
+    }
+    for bucket in scattered.iter_mut() {
+        let len = bucket.run_len.at(0);
+        buckets.place(bucket, len, records, 1, &mut writer, &mut target[0])?;
+        buckets.disk_ops+=bucket.disk_ops();
+    }
+    writer.finish(&mut target[0]);
+    target[0].run_len.set_single(writer.written);
+    target[0].flush()?;
+    buckets.disk_ops+=target[0].disk_ops();
+    println!();println!();
+    target[0].print();
+
+    let b: f32 = (BLOCK_SIZE/size_of::<R>()) as f32;
+    let teor = cost::distribution(buckets.n, records, buckets.budget as f32, b);
+    return Ok(SortInfo {
+        number_of_phases: buckets.levels + 1,
+        disk_ops: buckets.disk_ops,
+
+        teor_number_of_phases: teor.phases,
+        teor_disk_ops: teor.disk_ops,
+
+        ascending_runs: 0,
+        descending_runs: 0,
+        buckets: buckets.sorted,
+
+        comparisons: 0,
+        prefix_hits: 0,
+        eliminated: writer.eliminated,
+
+        in_memory: fits
+    })
+}
+
+struct Buckets<'c, R> {
+    n: usize,
+    /* records sorted in memory at once */
+    budget: usize,
+    cmp: Compare<'c, R>,
+    disk_ops: usize,
+    /* buckets that were written to the output */
+    sorted: usize,
+    /* deepest scatter or merge pass */
+    levels: usize
+}
+
+impl<R> Buckets<'_, R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    /* up to n-1 distinct keys that cut n*SAMPLES random records of the source into equal parts */
+    fn splitters(&mut self, source: &mut Tape<R>, len: usize) -> Result<Vec<R>, Error> {
+        let mut rng = rand::thread_rng();
+        let mut samples = Vec::new();
+        for _i in 0..self.n*SAMPLES {
+            let mut reader = source.run_reader_at(0, rng.gen_range(0..len))?;
+            samples.push(reader.view_record());
+            self.disk_ops+=reader.disk_ops();
+        }
+        samples.sort_by(self.cmp);
+        let mut splitters: Vec<R> = Vec::new();
+        for i in 1..self.n {
+            let sample = samples[i*samples.len()/self.n];
+            if splitters.last().is_none_or(|last| (self.cmp)(last, &sample) == Ordering::Less) {
+                splitters.push(sample);
+            }
+        }
+        return Ok(splitters);
+    }
+
+    /* reads the source once and writes every record onto the tape of its bucket,
+       bucket i holds the keys from splitter i-1 up to splitter i, None when the samples were all equal */
+    fn scatter(&mut self, source: &mut Tape<R>, len: usize) -> Result<Option<Vec<Tape<R>>>, Error> {
+        let splitters = self.splitters(source, len)?;
+        if splitters.is_empty() { return Ok(None); }
+        let mut buckets: Vec<Tape<R>> = c![Tape::new(), for _i in 0..splitters.len() + 1];
+        let mut lens = vec![0; buckets.len()];
+        while !source.is_empty() {
+            let record = source.next_record();
+            let bucket = splitters.partition_point(|splitter| (self.cmp)(splitter, &record) != Ordering::Greater);
+            buckets[bucket].push(record);
+            lens[bucket]+=1;
+        }
+        for (bucket, len) in buckets.iter_mut().zip(lens) {
+            bucket.run_len.set_single(len);
+            bucket.flush()?;
+        }
+        return Ok(Some(buckets));
+    }
+
+    /* writes the len records of bucket in order, parent_len is the size of the bucket it was cut from */
+    fn place(&mut self, bucket: &mut Tape<R>, len: usize, parent_len: usize, level: usize,
+             writer: &mut Writer<R>, output: &mut Tape<R>) -> Result<(), Error> {
+        if len <= self.budget {
+            let mut records = Vec::with_capacity(len);
+            /* an empty tape only finds out it is empty by reading, so the length is used */
+            for _i in 0..len { records.push(bucket.next_record()); }
+            records.sort_by(self.cmp);
+            for record in records { writer.push(record, output); }
+            self.sorted+=1;
+            return Ok(());
+        }
+        /* every level keeps its buckets open until they are written */
+        let fits = (level + 1)*self.n + 2 <= MAX_OPEN_TAPES;
+        let scattered = if fits && len < parent_len { self.scatter(bucket, len)? } else { None };
+        let Some(mut buckets) = scattered else {
+            /* a single run length would tell natural the bucket is already sorted */
+            bucket.run_len.clear();
+            let info = natural(slice::from_mut(bucket), self.n, self.cmp, &mut Reduce::Keep)?;
+            self.disk_ops+=info.disk_ops;
+            self.levels = self.levels.max(level + info.number_of_phases);
+            while !bucket.is_empty() { writer.push(bucket.next_record(), output); }
+            self.sorted+=1;
+            return Ok(());
+        };
+        self.levels = self.levels.max(level + 1);
+        for inner in buckets.iter_mut() {
+            let inner_len = inner.run_len.at(0);
+            self.place(inner, inner_len, len, level + 1, writer, output)?;
+            self.disk_ops+=inner.disk_ops();
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    io::{Error, Write},
+    cmp::Ordering,
+    fmt::{self, Display},
+    str::FromStr
+};
+use rand::{Rng, distributions::{Distribution, Standard}};
+use serde::{Serialize, Deserialize};
+use serde_derive::{Serialize, Deserialize};
+
+use super::{tape::Tape, cost::Algorithm, presorted::WriteRecord, Compare, SortInfo, sort_if_needed};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    /* every pair of records with equal keys */
+    Inner,
+    /* inner join and every left record without a match */
+    LeftOuter,
+    /* only the left records without a match */
+    Anti
+}
+
+impl Display for JoinKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            JoinKind::Inner => "inner",
+            JoinKind::LeftOuter => "left",
+            JoinKind::Anti => "anti",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for JoinKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "inner" => Ok(JoinKind::Inner),
+            "left" => Ok(JoinKind::LeftOuter),
+            "anti" => Ok(JoinKind::Anti),
+            _ => Err(format!("unknown join: {s}"))
+        }
+    }
+}
+
+/* one output row, a left record without a match has matches == 0 and a copy of itself as right,
+   u32 so a pair of triangles has no padding */
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Joined<R> {
+    pub left: R,
+    pub right: R,
+    /* size of the group of right records the pair comes from */
+    pub matches: u32
+}
+
+impl<R: PartialOrd> PartialOrd for Joined<R> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return match self.left.partial_cmp(&other.left) {
+            Some(Ordering::Equal) => self.right.partial_cmp(&other.right),
+            ordering => ordering
+        }
+    }
+}
+impl<R: PartialEq> PartialEq for Joined<R> {
+    fn eq(&self, other: &Self) -> bool {
+        return self.left == other.left && self.right == other.right && self.matches == other.matches;
+    }
+}
+
+impl<R: Display> Display for Joined<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.matches == 0 {
+            return write!(f, "{} | -", self.left);
+        }
+        write!(f, "{} | {}", self.left, self.right)
+    }
+}
+/* both records in the form load reads, - instead of the right one without a match */
+impl<R: WriteRecord> WriteRecord for Joined<R> {
+    fn write_record(&self, out: &mut dyn Write) -> Result<(), Error> {
+        self.left.write_record(out)?;
+        write!(out, " | ")?;
+        if self.matches == 0 {
+            return write!(out, "-");
+        }
+        self.right.write_record(out)
+    }
+}
+impl<R> Distribution<Joined<R>> for Standard
+where Standard: Distribution<R> {
+    fn sample<G: Rng + ?Sized>(&self, rng: &mut G) -> Joined<R> {
+        return Joined{ left: rng.gen(), right: rng.gen(), matches: 1 };
+    }
+}
+
+pub struct JoinInfo {
+    /* None when the input was already sorted by the join key */
+    pub left_sort: Option<SortInfo>,
+    pub right_sort: Option<SortInfo>,
+
+    pub matched: usize,
+    pub unmatched: usize,
+    /* most right records buffered at once */
+    pub largest_group: usize,
+    pub disk_ops: usize
+}
+
+/* sort-merge join of left[0] and right[0] on the key cmp compares, inputs that are not sorted by it
+   are sorted with algorithm and n tapes first, right records with equal keys are kept in memory */
+pub fn join<R>(left: &mut [Tape<R>], right: &mut [Tape<R>], output: &mut Tape<Joined<R>>, kind: JoinKind,
+               cmp: Compare<R>, algorithm: Algorithm, n: usize) -> Result<JoinInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    /* nothing can match: an inner join with an empty side or any join with an empty left side writes no row */
+    if left[0].record_count() == 0 || (kind == JoinKind::Inner && right[0].record_count() == 0) {
+        output.run_len.set_single(0);
+        output.flush()?;
+        return Ok(JoinInfo {
+            left_sort: None,
+            right_sort: None,
+
+            matched: 0,
+            unmatched: left[0].record_count(),
+            largest_group: 0,
+            disk_ops: 0
+        })
+    }
+    let left_sort = sort_if_needed(left, cmp, algorithm, n)?;
+    let right_sort = sort_if_needed(right, cmp, algorithm, n)?;
+    let before = left[0].disk_ops() + right[0].disk_ops() + output.disk_ops();
+
+    let mut group: Vec<R> = Vec::new();
+    let mut written = 0;
+    let mut matched = 0;
+    let mut unmatched = 0;
+    let mut largest_group = 0;
+    /* an empty tape only finds out it is empty by reading, so both sides are read by their record counts */
+    let mut right_left = right[0].record_count();
+    for _i in 0..left[0].record_count() {
+        let record = left[0].next_record();
+        /* the group is reused by every left record with the same key */
+        if group.first().is_none_or(|first| cmp(&record, first) != Ordering::Equal) {
+            group.clear();
+            while right_left > 0 && cmp(&right[0].view_record(), &record) == Ordering::Less {
+                right[0].next_record();
+                right_left-=1;
+            }
+            while right_left > 0 && cmp(&right[0].view_record(), &record) == Ordering::Equal {
+                group.push(right[0].next_record());
+                right_left-=1;
+            }
+            largest_group = largest_group.max(group.len());
+        }
+
+        if group.is_empty() {
+            if kind != JoinKind::Inner {
+                output.push(Joined{ left: record, right: record, matches: 0 });
+                written+=1;
+            }
+            unmatched+=1;
+            continue;
+        }
+        if kind != JoinKind::Anti {
+            for &other in &group {
+                output.push(Joined{ left: record, right: other, matches: group.len() as u32 });
+            }
+            matched+=group.len();
+            written+=group.len();
+        }
+    }
+    output.run_len.set_single(written);
+    output.flush()?;
+    left[0].flush()?;
+    right[0].flush()?;
+
+    return Ok(JoinInfo {
+        left_sort,
+        right_sort,
+
+        matched,
+        unmatched,
+        largest_group,
+        disk_ops: left[0].disk_ops() + right[0].disk_ops() + output.disk_ops() - before
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::triangle::Triangle;
+
+    fn tape(records: &[&str]) -> Vec<Tape<Triangle>> {
+        return vec![records.iter().map(|record| record.parse::<Triangle>().unwrap()).collect()];
+    }
+    fn rows(output: &mut Tape<Joined<Triangle>>) -> Vec<([(i32, i32); 3], u32)> {
+        output.rewind().unwrap();
+        return output.iter().map(|row| row.map(|row| (row.left.vertices(), row.matches))).collect::<Result<_, _>>().unwrap();
+    }
+
+    const RECORDS: [&str; 3] = ["{(0,0),(2,0),(0,2)}", "{(0,0),(1,0),(0,1)}", "{(0,0),(1,0),(0,1)}"];
+
+    #[test]
+    fn empty_left() {
+        for kind in [JoinKind::Inner, JoinKind::LeftOuter, JoinKind::Anti] {
+            let (mut left, mut right, mut output) = (tape(&[]), tape(&RECORDS), Tape::new());
+            let info = join(&mut left, &mut right, &mut output, kind, &Triangle::cmp, Algorithm::Natural, 3).unwrap();
+            assert_eq!((info.matched, info.unmatched), (0, 0));
+            assert!(rows(&mut output).is_empty());
+        }
+    }
+
+    #[test]
+    fn empty_right() {
+        for kind in [JoinKind::Inner, JoinKind::LeftOuter, JoinKind::Anti] {
+            let (mut left, mut right, mut output) = (tape(&RECORDS), tape(&[]), Tape::new());
+            let info = join(&mut left, &mut right, &mut output, kind, &Triangle::cmp, Algorithm::Natural, 3).unwrap();
+            assert_eq!((info.matched, info.unmatched), (0, 3));
+            let rows = rows(&mut output);
+            if kind == JoinKind::Inner {
+                assert!(rows.is_empty());
+                continue;
+            }
+            /* every left record once, sorted and without a match */
+            let mut expected: Vec<Triangle> = RECORDS.iter().map(|record| record.parse().unwrap()).collect();
+            expected.sort();
+            assert_eq!(rows, expected.into_iter().map(|record| (record.vertices(), 0)).collect::<Vec<_>>());
+        }
+    }
+}
@@ -1,9 +1,9 @@
 use std::{
     io::Error,
-    cmp::{PartialOrd, max},
+    cmp::{PartialOrd, Ordering, max},
     marker::Copy,
     fmt::Display,
-    sync::Mutex, str::FromStr, mem::size_of
+    sync::Mutex, str::FromStr, mem::size_of, cell::Cell
 };
 use rand::distributions::{Distribution, Standard};
 use serde::{Serialize, Deserialize};
@@ -12,11 +12,54 @@ use cute::c;
 
 use crate::file_handler::BLOCK_SIZE;
 
-use self::tape::Tape;
+use self::tape::{Tape, RecordSource};
+use self::cost::Algorithm;
+use self::reduce::{Reduce, Writer, identical_order};
 
 pub mod tape;
+pub mod run_index;
+pub mod cost;
+pub mod balanced;
+pub mod cascade;
+pub mod oscillating;
+pub mod planner;
+pub mod stable;
+pub mod prefix;
+pub mod topk;
+pub mod reduce;
+pub mod presorted;
+pub mod join;
+pub mod setops;
+pub mod parallel;
+pub mod distribution;
+pub mod radix;
+pub mod adaptive;
+pub mod sorted;
+
+/* order used by every strategy, so records can be sorted by more than their PartialOrd */
+pub type Compare<'c, R> = &'c dyn Fn(&R, &R) -> Ordering;
+/* Compare that worker threads can use at the same time */
+pub type SharedCompare<'c, R> = &'c (dyn Fn(&R, &R) -> Ordering + Sync);
 
 pub static DISPLAY_AFTER_RUN: Mutex<bool> = Mutex::new(true);
+/* tapes print their records, sorts that do not work on the given tape turn this off */
+pub static PRINT_TAPES: Mutex<bool> = Mutex::new(true);
+/* pages of records a sort may keep in memory at once */
+pub static MEMORY_PAGES: Mutex<usize> = Mutex::new(10);
+/* tapes are used even when the input fits in MEMORY_PAGES */
+pub static FORCE_EXTERNAL: Mutex<bool> = Mutex::new(false);
+
+/* records of type R that fit in MEMORY_PAGES */
+pub fn memory_records<R>() -> usize {
+    return *MEMORY_PAGES.lock().unwrap()*BLOCK_SIZE/size_of::<R>();
+}
+
+/* the tape is sorted in memory instead of on tapes */
+pub fn fits_in_memory<R>(tape: &Tape<R>) -> bool
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    let external = *FORCE_EXTERNAL.lock().unwrap();
+    return tape.record_count() <= memory_records::<R>() && !external;
+}
 
 #[macro_export]
 macro_rules! display_after_run {
@@ -35,29 +78,114 @@ pub struct SortInfo {
     pub disk_ops: usize,
 
     pub teor_number_of_phases: f32,
-    pub teor_disk_ops: f32
+    pub teor_disk_ops: f32,
+
+    pub ascending_runs: usize,
+    pub descending_runs: usize,
+    /* pieces a distribution sort cut the input into and wrote one after another, it reads no runs */
+    pub buckets: usize,
+
+    /* comparisons made by the sort, and how many of them the key prefix alone decided */
+    pub comparisons: usize,
+    pub prefix_hits: usize,
+
+    /* records dropped or combined with an equal one by the final merge */
+    pub eliminated: usize,
 
+    /* the input fit in MEMORY_PAGES and was sorted without tapes */
+    pub in_memory: bool
 }
 
+/* natural runs found by the first distribution */
+#[derive(Clone, Copy, Default)]
+pub struct Runs {
+    pub ascending: usize,
+    pub descending: usize,
+    pub records: usize
+}
+
+impl Runs {
+    pub fn total(&self) -> usize { return (self.ascending + self.descending).max(1); }
+    fn add(&mut self, len: usize, descending: bool) {
+        if len == 0 { return; }
+        if descending { self.descending+=1; } else { self.ascending+=1; }
+        self.records+=len;
+    }
+}
+
+/* a natural run is either non-decreasing or strictly decreasing */
+#[derive(Clone, Copy)]
+enum RunState<R> {
+    Empty,
+    Single(R),
+    Ascending(R),
+    Descending(R)
+}
+
+impl<R: Copy> RunState<R> {
+    fn extends(&self, record: &R, cmp: Compare<R>) -> bool {
+        return match self {
+            RunState::Empty | RunState::Single(_) => true,
+            RunState::Ascending(last) => cmp(record, last) != Ordering::Less,
+            RunState::Descending(last) => cmp(record, last) == Ordering::Less
+        }
+    }
+    fn push(self, record: R, cmp: Compare<R>) -> Self {
+        return match self {
+            RunState::Empty => RunState::Single(record),
+            RunState::Single(last) if cmp(&record, &last) == Ordering::Less => RunState::Descending(record),
+            RunState::Single(_) | RunState::Ascending(_) => RunState::Ascending(record),
+            RunState::Descending(_) => RunState::Descending(record)
+        }
+    }
+    fn is_descending(&self) -> bool { return matches!(self, RunState::Descending(_)); }
+}
+
+impl SortInfo {
+    pub fn phases_error(&self) -> f32 {
+        return cost::relative_error(self.number_of_phases as f32, self.teor_number_of_phases);
+    }
+    pub fn disk_ops_error(&self) -> f32 {
+        return cost::relative_error(self.disk_ops as f32, self.teor_disk_ops);
+    }
+}
+
+
+pub fn sort<R>(target: &mut [Tape<R>], n: usize) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    return sort_by(target, n, &natural_order);
+}
+
+/* natural merge sort in the order given by cmp instead of the records' PartialOrd */
+pub fn sort_by<R>(target: &mut [Tape<R>], n: usize, cmp: Compare<R>) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    return sort_with(Algorithm::Natural, target, n, cmp);
+}
 
-pub fn sort<R>(target: &mut Vec<Tape<R>>, n: usize) -> Result<SortInfo, Error>
+fn natural<R>(target: &mut [Tape<R>], n: usize, cmp: Compare<R>, reduce: &mut Reduce<R>) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    return natural_with(target, n, cmp, |tapes, output| Ok(merge_last(tapes, output, cmp, reduce)));
+}
+
+/* natural merge sort, last does the merge that leaves a single run and returns how many records it eliminated */
+fn natural_with<R>(target: &mut [Tape<R>], n: usize, cmp: Compare<R>, mut last: impl FnMut(&mut [Tape<R>], &mut Tape<R>) -> Result<usize, Error>) -> Result<SortInfo, Error>
 where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
     let mut disk_ops = 0;
     let mut number_of_phases = 0;
-    let mut initial_runs = 1;
-    let mut record_num = 1;
+    let mut eliminated = 0;
+    let mut runs = Runs::default();
     let mut assigned = false;
     target[0].print();
 
     let mut tapes: Vec<Tape<R>> = c![Tape::new(), for _i in 0..n];
     while !is_sorted(target) {
         if !assigned {
-            (initial_runs, record_num) = distribute(&mut target[0], &mut tapes);
+            runs = distribute(&mut target[0], &mut tapes, cmp);
             assigned = true;
         } else {
-            distribute(&mut target[0], &mut tapes);
+            distribute(&mut target[0], &mut tapes, cmp);
         }
-        for i in 0..target.len() { disk_ops+=target[i].disk_ops() }
+        for tape in target.iter_mut() { disk_ops+=tape.disk_ops() }
         clear_tapes(target);
 
 
@@ -66,12 +194,17 @@ where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Stan
             print_info(&mut tapes);
             println!();println!();
         }
-        merge(&mut tapes, target);
+        /* one run left on every tape: this is the last merge */
+        if max_run(&mut tapes) <= 1 {
+            eliminated = last(&mut tapes, &mut target[0])?;
+        } else {
+            merge(&mut tapes, target, cmp);
+        }
         if *display {
             print_info(target);
         }
 
-        for i in 0..tapes.len() { disk_ops+=tapes[i].disk_ops() }
+        for tape in tapes.iter_mut() { disk_ops+=tape.disk_ops() }
         
         clear_tapes(&mut tapes);
         number_of_phases+=1;
@@ -81,56 +214,225 @@ where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Stan
 
 
     let b: f32 = (BLOCK_SIZE/size_of::<R>()) as f32;
+    let teor = cost::natural(n, runs.total(), runs.records, b);
     return Ok(SortInfo {
         number_of_phases,
         disk_ops,
 
-        teor_number_of_phases: (initial_runs as f32).log2().ceil(),
-        teor_disk_ops: 4_f32*(record_num as f32)*((initial_runs as f32).log2().ceil())/b
+        teor_number_of_phases: teor.phases,
+        teor_disk_ops: teor.disk_ops,
+
+        ascending_runs: runs.ascending,
+        descending_runs: runs.descending,
+        buckets: 0,
+
+        comparisons: 0,
+        prefix_hits: 0,
+        eliminated,
+
+        in_memory: false
     })
 }
 
-fn print_info<R>(tapes: &mut Vec<Tape<R>>)
+pub fn sort_with<R>(algorithm: Algorithm, target: &mut [Tape<R>], n: usize, cmp: Compare<R>) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    return sort_reduced(algorithm, target, n, cmp, &mut Reduce::Keep);
+}
+
+/* reduce is applied by the last merge, so dropping or combining equal records needs no pass of its own */
+pub fn sort_reduced<R>(algorithm: Algorithm, target: &mut [Tape<R>], n: usize, cmp: Compare<R>, reduce: &mut Reduce<R>) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let identical = identical_order(cmp);
+    let cmp: Compare<R> = if matches!(reduce, Reduce::DropIdentical) { &identical } else { cmp };
+    let comparisons = Cell::new(0);
+    let counted = |a: &R, b: &R| {
+        comparisons.set(comparisons.get() + 1);
+        cmp(a, b)
+    };
+    let cmp: Compare<R> = &counted;
+    let fits = fits_in_memory(&target[0]);
+    let mut info = match algorithm {
+        _ if fits && algorithm != Algorithm::Polyphase => in_memory(target, cmp, reduce),
+        Algorithm::Natural => natural(target, n, cmp, reduce),
+        Algorithm::Balanced => balanced::sort(target, n, cmp, reduce),
+        Algorithm::Cascade => cascade::sort(target, n, cmp, reduce),
+        Algorithm::Oscillating => oscillating::sort(target, n, cmp, reduce),
+        Algorithm::Planned => planner::sort(target, n, cmp, reduce),
+        Algorithm::Distribution => distribution::sort(target, n, cmp, reduce),
+        Algorithm::Polyphase => Err(Error::other("polyphase merge has only a cost model"))
+    }?;
+    info.comparisons = comparisons.get();
+    return Ok(info);
+}
+
+/* input that fits in memory is read once, sorted without tapes and written once */
+fn in_memory<R>(target: &mut [Tape<R>], cmp: Compare<R>, reduce: &mut Reduce<R>) -> Result<SortInfo, Error>
 where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
-    let mut tape_num = 0;
-    for i in 0..tapes.len() {
+    let records = target[0].record_count();
+    target[0].print();
+    let mut sorted = Vec::with_capacity(records);
+    for _i in 0..records { sorted.push(target[0].next_record()); }
+    let mut disk_ops = target[0].disk_ops();
+    clear_tapes(target);
+
+    sorted.sort_by(cmp);
+    let mut writer = Writer::new(reduce, cmp);
+    for record in sorted { writer.push(record, &mut target[0]); }
+    writer.finish(&mut target[0]);
+    target[0].run_len.set_single(writer.written);
+    target[0].flush()?;
+    disk_ops+=target[0].disk_ops();
+    println!();println!();
+    target[0].print();
+
+    let b: f32 = (BLOCK_SIZE/size_of::<R>()) as f32;
+    let teor = cost::in_memory(records, b);
+    return Ok(SortInfo {
+        number_of_phases: 1,
+        disk_ops,
+
+        teor_number_of_phases: teor.phases,
+        teor_disk_ops: teor.disk_ops,
+
+        ascending_runs: 0,
+        descending_runs: 0,
+        buckets: 0,
+
+        comparisons: 0,
+        prefix_hits: 0,
+        eliminated: writer.eliminated,
+
+        in_memory: true
+    })
+}
+
+/* records are ordered by the key extracted from them, not by their PartialOrd */
+pub fn sort_by_key<R, K, F>(algorithm: Algorithm, target: &mut [Tape<R>], n: usize, key: F) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R>,
+      K: PartialOrd, F: Fn(&R) -> K {
+    return sort_with(algorithm, target, n, &|a, b| natural_order(&key(a), &key(b)));
+}
+
+/* equal records keep their input order, so identical records cannot be brought together to drop them */
+pub fn sort_stable<R>(algorithm: Algorithm, target: &mut [Tape<R>], n: usize, cmp: Compare<R>, reduce: &mut Reduce<R>) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    if matches!(reduce, Reduce::DropIdentical) {
+        return Err(Error::other("a stable sort keeps equal keys in input order, identical records are only dropped by an unstable one"));
+    }
+    let stamped_cmp = stable::stamped_order(cmp);
+    return stable::sort(target, cmp, reduce, |stamped| sort_with::<stable::Stamped<R>>(algorithm, stamped, n, &stamped_cmp));
+}
+
+/* the records' own order, incomparable records count as equal */
+pub fn natural_order<R: PartialOrd>(a: &R, b: &R) -> Ordering {
+    return a.partial_cmp(b).unwrap_or(Ordering::Equal);
+}
+
+/* cmp turned around, sorts in descending order without touching the records */
+pub fn reverse<R, F: Fn(&R, &R) -> Ordering>(cmp: F) -> impl Fn(&R, &R) -> Ordering {
+    return move |a, b| cmp(b, a);
+}
+
+/* initial runs guessed from the first page of the tape */
+pub fn estimate_runs<R>(tape: &mut Tape<R>, cmp: Compare<R>) -> Result<usize, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let records = tape.record_count();
+    let mut sampled = 0;
+    let mut runs = 1;
+    let mut state = RunState::Empty;
+    while !tape.is_empty() && sampled < BLOCK_SIZE/size_of::<R>() {
+        let record = tape.next_record();
+        if !state.extends(&record, cmp) {
+            runs+=1;
+            state = RunState::Empty;
+        }
+        state = state.push(record, cmp);
+        sampled+=1;
+    }
+    /* only part of the tape was read, flushing would write the rest of the page back */
+    tape.rewind()?;
+    if sampled == 0 { return Ok(1); }
+    return Ok((records*runs).div_ceil(sampled));
+}
+
+fn print_info<R>(tapes: &mut [Tape<R>])
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    for (tape_num, tape) in tapes.iter_mut().enumerate() {
         println!("t{tape_num}");
-        tape_num+=1;
-        tapes[i].print();
+        tape.print();
     }
 }
 
-fn distribute<R>(source: &mut Tape<R>, target: &mut Vec<Tape<R>>) -> (usize, usize)
+fn distribute<R>(source: &mut Tape<R>, target: &mut [Tape<R>], cmp: Compare<R>) -> Runs
 where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
-    let mut i = 0;
-    let mut number_of_runs = 1;
-    let mut last_record: Option<R> = None;
-    let mut run = 0;
-    let mut n = 0;
-    
+    let len = target.len();
+    let mut i = len - 1;
+    return distribute_with(source, target, cmp, || { i = (i+1)%len; i }); /* cycle through targets */
+}
+
+/* writes every natural run of source onto the tape picked by select */
+fn distribute_with<R>(source: &mut Tape<R>, target: &mut [Tape<R>], cmp: Compare<R>, mut select: impl FnMut() -> usize) -> Runs
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let mut runs = Runs::default();
+
     while !source.is_empty() {
-        let record: R = source.next_record();
-        if Some(record) < last_record {
-            number_of_runs+=1;
-            target[i].run_len.push(run);
-            i = (i+1)%(target.len()); /* cycle through targets */
-            run = 0;
-        }
-        target[i].push(record);
-        last_record = Some(record);
-        run+=1;
-        n+=1;
+        let i = select();
+        copy_run(source, &mut target[i], &mut runs, cmp);
+    }
+    if runs.total() == 1 && runs.records == 0 {
+        target[select()].run_len.push(0);
     }
-    target[i].run_len.push(run);
 
     for tape in target {
         tape.flush().expect("cannot flush tape");
     }
-    return (number_of_runs,n);
+    return runs;
+}
+
+/* copies the next natural run of source onto target, descending runs are written reversed */
+fn copy_run<R>(source: &mut Tape<R>, target: &mut Tape<R>, runs: &mut Runs, cmp: Compare<R>) -> usize
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let page = BLOCK_SIZE/size_of::<R>();
+    let mut state = RunState::Empty;
+    let mut run = 0;
+    /* records that may still have to be reversed, pages of a long descending run go to spill */
+    let mut buffer: Vec<R> = Vec::new();
+    let mut spill: Option<Tape<R>> = None;
+
+    while !source.is_empty() {
+        let record: R = source.view_record();
+        if !state.extends(&record, cmp) { break; }
+        state = state.push(source.next_record(), cmp);
+        run+=1;
+
+        if let RunState::Ascending(_) = state {
+            for buffered in buffer.drain(..) { target.push(buffered); }
+            target.push(record);
+            continue;
+        }
+        buffer.push(record);
+        if buffer.len() == page {
+            let spill = spill.get_or_insert_with(Tape::new);
+            for buffered in buffer.drain(..) { spill.push(buffered); }
+        }
+    }
+
+    for buffered in buffer.drain(..).rev() { target.push(buffered); }
+    if let Some(mut spill) = spill {
+        let spilled = run - run%page;
+        for start in (0..spilled).step_by(page).rev() {
+            spill.seek_record(start).expect("cannot read spilled run");
+            for _ in 0..page { buffer.push(spill.next_record()); }
+            for buffered in buffer.drain(..).rev() { target.push(buffered); }
+        }
+    }
+    target.run_len.push(run);
+    runs.add(run, state.is_descending());
+    return run;
 }
 
 
-fn merge<R>(tapes: &mut Vec<Tape<R>>, target: &mut Vec<Tape<R>>)
+fn merge<R>(tapes: &mut [Tape<R>], target: &mut [Tape<R>], cmp: Compare<R>)
 where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+FromStr+Copy, Standard: Distribution<R> {
     /* from source tapes to target tapes */
     let mut target_idx = 0;
@@ -141,14 +443,14 @@ where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+FromStr+Copy, Stan
             let mut min_record: Option<R> = None;
             let mut tape_idx = 0;
             for i in 0..tapes.len() {
-                if tapes[i].run_len.len() <= run || idx[i] >= tapes[i].run_len[run] { continue; }
+                if tapes[i].run_len.get(run).is_none_or(|len| idx[i] >= len) { continue; }
                 let obj = tapes[i].view_record();
-                if min_record == None || Some(obj) <= min_record {
+                if min_record.as_ref().is_none_or(|min| cmp(&obj, min) != Ordering::Greater) {
                     min_record = Some(obj);
                     tape_idx = i;
                 }
             }
-            if min_record == None { break; }
+            if min_record.is_none() { break; }
             target[target_idx].push(tapes[tape_idx].next_record());
             idx[tape_idx] += 1;
         }
@@ -161,7 +463,87 @@ where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+FromStr+Copy, Stan
 
 }
 
-fn is_sorted<R>(tapes: &mut Vec<Tape<R>>)-> bool 
+/* merges one run from every input (tape, run length) into a single run on output */
+fn merge_run<R, S>(inputs: &mut [(S, usize)], output: &mut Tape<R>, cmp: Compare<R>) -> usize
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+FromStr+Copy, Standard: Distribution<R>, S: RecordSource<R> {
+    return merge_run_limit(inputs, output, cmp, usize::MAX);
+}
+
+/* like merge_run, but stops after limit records and leaves the rest of the inputs unread */
+fn merge_run_limit<R, S>(inputs: &mut [(S, usize)], output: &mut Tape<R>, cmp: Compare<R>, limit: usize) -> usize
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+FromStr+Copy, Standard: Distribution<R>, S: RecordSource<R> {
+    let mut merged = 0;
+    while merged < limit {
+        let Some(tape_idx) = smallest(inputs, cmp) else { break; };
+        output.push(inputs[tape_idx].0.next_record());
+        inputs[tape_idx].1 -= 1;
+        merged += 1;
+    }
+    output.run_len.push(merged);
+    return merged;
+}
+
+/* merge_run for the last merge of a sort, returns how many records reduce eliminated */
+fn merge_run_reduce<R, S>(inputs: &mut [(S, usize)], output: &mut Tape<R>, cmp: Compare<R>, reduce: &mut Reduce<R>) -> usize
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+FromStr+Copy, Standard: Distribution<R>, S: RecordSource<R> {
+    let mut writer = Writer::new(reduce, cmp);
+    while let Some(tape_idx) = smallest(inputs, cmp) {
+        writer.push(inputs[tape_idx].0.next_record(), output);
+        inputs[tape_idx].1 -= 1;
+    }
+    writer.finish(output);
+    output.run_len.push(writer.written);
+    return writer.eliminated;
+}
+
+/* merges the only run of every tape onto output */
+fn merge_last<R>(tapes: &mut [Tape<R>], output: &mut Tape<R>, cmp: Compare<R>, reduce: &mut Reduce<R>) -> usize
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+FromStr+Copy, Standard: Distribution<R> {
+    let mut runs: Vec<(&mut Tape<R>, usize)> = tapes.iter_mut()
+        .filter_map(|tape| tape.run_len.first().map(|len| (tape, len)))
+        .collect();
+    let eliminated = merge_run_reduce(&mut runs, output, cmp, reduce);
+    output.flush().expect("cannot flush tape");
+    return eliminated;
+}
+
+/* input whose next record comes first, None when every run is used up */
+fn smallest<R, S>(inputs: &mut [(S, usize)], cmp: Compare<R>) -> Option<usize>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+FromStr+Copy, Standard: Distribution<R>, S: RecordSource<R> {
+    let mut min_record: Option<R> = None;
+    let mut tape_idx = None;
+    for (i, (tape, left)) in inputs.iter_mut().enumerate() {
+        if *left == 0 { continue; }
+        let obj = tape.view_record();
+        if min_record.as_ref().is_none_or(|min| cmp(&obj, min) != Ordering::Greater) {
+            min_record = Some(obj);
+            tape_idx = Some(i);
+        }
+    }
+    return tape_idx;
+}
+
+/* sorts target[0] with algorithm only when it is not sorted by cmp yet */
+fn sort_if_needed<R>(target: &mut [Tape<R>], cmp: Compare<R>, algorithm: Algorithm, n: usize) -> Result<Option<SortInfo>, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    target[0].flush()?;
+    let mut previous: Option<R> = None;
+    let mut sorted = true;
+    /* an empty tape only finds out it is empty by reading, so the records come from iter */
+    for record in target[0].iter() {
+        let record = record?;
+        sorted = previous.is_none_or(|previous| cmp(&record, &previous) != Ordering::Less);
+        previous = Some(record);
+        if !sorted { break; }
+    }
+    target[0].rewind()?;
+    if sorted {
+        return Ok(None);
+    }
+    return Ok(Some(sort_with(algorithm, target, n, cmp)?));
+}
+
+fn is_sorted<R>(tapes: &mut [Tape<R>])-> bool 
 where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+FromStr, Standard: Distribution<R> {
     for tape in tapes {
         if tape.run_len.len() != 1 {return false;} /* if only one run remains it means it is sorted */
@@ -169,18 +551,18 @@ where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+FromStr, Standard:
     return true;
 }
 
-fn clear_tapes<R>(tapes: &mut Vec<Tape<R>>)
+fn clear_tapes<R>(tapes: &mut [Tape<R>])
 where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+FromStr, Standard: Distribution<R> {
-    for i in 0..tapes.len() {
-        tapes[i].clear().expect("TODO: panic message");
+    for tape in tapes {
+        tape.clear().expect("TODO: panic message");
     }
 }
 
-fn max_run<R>(tapes: &mut Vec<Tape<R>>) -> usize
+fn max_run<R>(tapes: &mut [Tape<R>]) -> usize
 where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+FromStr, Standard: Distribution<R> {
     let mut max_run = None;
-    for i in 0..tapes.len() {
-        max_run = max(max_run, Some(tapes[i].run_len.len()));
+    for tape in tapes {
+        max_run = max(max_run, Some(tape.run_len.len()));
     }
-    return if let Some(ret) = max_run {ret.clone()} else {0};
+    return max_run.unwrap_or(0);
 }
\ No newline at end of file
@@ -0,0 +1,126 @@
+use std::{
+    io::Error,
+    fmt::Display,
+    str::FromStr,
+    mem::size_of
+};
+use rand::distributions::{Distribution, Standard};
+use serde::{Serialize, Deserialize};
+use cute::c;
+
+use crate::file_handler::BLOCK_SIZE;
+
+use super::{tape::Tape, cost, Compare, SortInfo, Runs, DISPLAY_AFTER_RUN, copy_run, merge_run, merge_run_reduce, clear_tapes, print_info};
+use super::reduce::Reduce;
+
+/* oscillating sort: n tapes used as stacks of runs, distribution and merging alternate */
+pub fn sort<R>(target: &mut [Tape<R>], n: usize, cmp: Compare<R>, reduce: &mut Reduce<R>) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let n = n.max(3);
+    let mut disk_ops = 0;
+    let eliminated;
+    let mut runs = Runs::default();
+    target[0].print();
+
+    let mut tapes: Vec<Tape<R>> = c![Tape::new(), for _i in 0..n];
+
+    /* result holds one run made of (n-1)^level initial runs */
+    let display = DISPLAY_AFTER_RUN.lock().unwrap();
+    let mut result = 0;
+    let mut level = 0;
+    copy_run(&mut target[0], &mut tapes[result], &mut runs, cmp);
+    loop {
+        let mut inputs = vec![result];
+        if !target[0].is_empty() {
+            let output = (result+1)%n;
+            inputs.extend((0..n).filter(|&t| t != result && t != output));
+            for &t in &inputs[1..] {
+                produce(&mut target[0], &mut tapes, level, t, &mut runs, cmp)?;
+            }
+            if !target[0].is_empty() {
+                merge_top_runs(&mut tapes, &inputs, output, cmp)?;
+                result = output;
+                level+=1;
+                if *display {
+                    print_info(&mut tapes);
+                    println!();println!();
+                }
+                continue;
+            }
+        }
+
+        /* source is used up: the last merge goes straight onto the target tape */
+        for tape in target.iter_mut() { disk_ops+=tape.disk_ops() }
+        clear_tapes(target);
+        let mut runs = Vec::new();
+        for (t, tape) in tapes.iter_mut().enumerate() {
+            if inputs.contains(&t) {
+                let len = tape.open_last_run()?;
+                runs.push((tape, len));
+            }
+        }
+        eliminated = merge_run_reduce(&mut runs, &mut target[0], cmp, reduce);
+        target[0].flush()?;
+        break;
+    }
+    for tape in tapes.iter_mut() { disk_ops+=tape.disk_ops() }
+    for tape in target.iter_mut() { disk_ops+=tape.disk_ops() }
+    if *display {
+        print_info(target);
+    }
+    println!();println!();
+    target[0].print();
+
+    let b: f32 = (BLOCK_SIZE/size_of::<R>()) as f32;
+    let teor = cost::oscillating(n, runs.total(), runs.records, b);
+    return Ok(SortInfo {
+        number_of_phases: level+1,
+        disk_ops,
+
+        teor_number_of_phases: teor.phases,
+        teor_disk_ops: teor.disk_ops,
+
+        ascending_runs: runs.ascending,
+        descending_runs: runs.descending,
+        buckets: 0,
+
+        comparisons: 0,
+        prefix_hits: 0,
+        eliminated,
+
+        in_memory: false
+    })
+}
+
+/* pushes one run of the given level onto tapes[out], leaving the other stacks as they were */
+fn produce<R>(source: &mut Tape<R>, tapes: &mut [Tape<R>], level: usize, out: usize, runs: &mut Runs, cmp: Compare<R>) -> Result<(), Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    if level == 0 {
+        copy_run(source, &mut tapes[out], runs, cmp);
+        return Ok(());
+    }
+    let inputs: Vec<usize> = (0..tapes.len()).filter(|&t| t != out).collect();
+    for &t in &inputs {
+        produce(source, tapes, level-1, t, runs, cmp)?;
+    }
+    return merge_top_runs(tapes, &inputs, out, cmp);
+}
+
+fn merge_top_runs<R>(tapes: &mut [Tape<R>], inputs: &[usize], output: usize, cmp: Compare<R>) -> Result<(), Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let mut runs = Vec::new();
+    let mut out = None;
+    for (t, tape) in tapes.iter_mut().enumerate() {
+        if t == output {
+            out = Some(tape);
+        } else if inputs.contains(&t) {
+            let len = tape.open_last_run()?;
+            runs.push((tape, len));
+        }
+    }
+    merge_run(&mut runs, out.unwrap(), cmp);
+    for &t in inputs {
+        tapes[t].pop_run()?;
+    }
+    Ok(())
+}
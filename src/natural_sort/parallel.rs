@@ -0,0 +1,259 @@
+use std::{
+    io::Error,
+    cmp::Ordering,
+    fmt::Display,
+    str::FromStr,
+    mem::size_of,
+    sync::{mpsc, atomic::{self, AtomicUsize}},
+    thread
+};
+use rand::distributions::{Distribution, Standard};
+use serde::{Serialize, Deserialize};
+use cute::c;
+
+use crate::file_handler::BLOCK_SIZE;
+
+use super::{tape::{Tape, RecordSource, MAX_OPEN_TAPES}, cost, SharedCompare, SortInfo, DISPLAY_AFTER_RUN, natural_with, merge, merge_run, is_sorted, max_run, clear_tapes, print_info};
+
+/* samples taken from every run for each partition */
+pub const SAMPLES: usize = 4;
+/* partitions of one merge, every partition needs two readers and a tape of its own at least */
+pub const MAX_PARTITIONS: usize = MAX_OPEN_TAPES/6;
+
+/* runs merge_partitioned merges at once: every partition has a reader on each of them and its own tape,
+   half of MAX_OPEN_TAPES is left for the tapes of the sort */
+pub fn fan_in(partitions: usize) -> usize {
+    return (MAX_OPEN_TAPES/2/partitions.clamp(1, MAX_PARTITIONS)).saturating_sub(1).max(2);
+}
+
+/* natural merge sort whose first pass is done by worker threads: target[0] is cut into chunks of chunk_len records,
+   every thread sorts its chunks in memory and writes them as runs onto its own tape,
+   the runs of all threads are merged and the rest is the usual natural merge with n tapes,
+   merges that leave a single run are split by key range between the threads */
+pub fn sort<R>(target: &mut [Tape<R>], n: usize, threads: usize, chunk_len: usize, cmp: SharedCompare<R>) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr+Send, Standard: Distribution<R> {
+    let threads = threads.max(1);
+    let chunk_len = chunk_len.max(1);
+    let comparisons = AtomicUsize::new(0);
+    let counted = |a: &R, b: &R| {
+        comparisons.fetch_add(1, atomic::Ordering::Relaxed);
+        cmp(a, b)
+    };
+    let records = target[0].record_count();
+    target[0].print();
+
+    let mut workers: Vec<Tape<R>> = c![Tape::new(), for _i in 0..threads];
+    let chunks = thread::scope(|scope| {
+        let mut senders = Vec::new();
+        for tape in workers.iter_mut() {
+            /* no queue: a thread holds only the chunk it is sorting */
+            let (sender, receiver) = mpsc::sync_channel::<Vec<R>>(0);
+            senders.push(sender);
+            let counted = &counted;
+            scope.spawn(move || {
+                for mut chunk in receiver {
+                    chunk.sort_by(counted);
+                    tape.run_len.push(chunk.len());
+                    for record in chunk { tape.push(record); }
+                }
+            });
+        }
+        let mut chunks = 0;
+        while !target[0].is_empty() {
+            let mut chunk = Vec::with_capacity(chunk_len);
+            while chunk.len() < chunk_len && !target[0].is_empty() {
+                chunk.push(target[0].next_record());
+            }
+            senders[chunks%threads].send(chunk).expect("worker thread stopped");
+            chunks+=1;
+        }
+        chunks
+    });
+    let mut disk_ops = 0;
+    for tape in workers.iter_mut() { tape.flush()?; }
+    for tape in target.iter_mut() { disk_ops+=tape.disk_ops() }
+    clear_tapes(target);
+
+    let display = DISPLAY_AFTER_RUN.lock().unwrap();
+    if *display {
+        print_info(&mut workers);
+        println!();println!();
+    }
+    if max_run(&mut workers) <= 1 {
+        disk_ops+=merge_partitioned(&mut workers, &mut target[0], threads, &counted)?;
+    } else {
+        merge(&mut workers, target, &counted);
+    }
+    for tape in workers.iter_mut() { disk_ops+=tape.disk_ops() }
+    if *display {
+        print_info(target);
+    }
+    drop(display);
+
+    let mut number_of_phases = 1;
+    if !is_sorted(target) {
+        let mut partitioned_ops = 0;
+        let rest = natural_with(target, n, &counted, |tapes, output| {
+            partitioned_ops+=merge_partitioned(tapes, output, threads, &counted)?;
+            Ok(0)
+        })?;
+        number_of_phases+=rest.number_of_phases;
+        disk_ops+=rest.disk_ops + partitioned_ops;
+    } else {
+        disk_ops+=target[0].disk_ops();
+        println!();println!();
+        target[0].print();
+    }
+
+    let b: f32 = (BLOCK_SIZE/size_of::<R>()) as f32;
+    let teor = cost::parallel(threads, n, chunks, records, b);
+    return Ok(SortInfo {
+        number_of_phases,
+        disk_ops,
+
+        teor_number_of_phases: teor.phases,
+        teor_disk_ops: teor.disk_ops,
+
+        ascending_runs: chunks,
+        descending_runs: 0,
+        buckets: 0,
+
+        comparisons: comparisons.load(atomic::Ordering::Relaxed),
+        prefix_hits: 0,
+        eliminated: 0,
+
+        in_memory: false
+    })
+}
+
+/* merges the only run of every tape onto output: splitter keys sampled from the runs cut the key space into partitions,
+   every partition is merged on its own thread onto its own tape and the tapes are copied onto output one after another,
+   records with equal keys always land in the same partition, so the result is the one of a serial merge,
+   more tapes than fan_in are merged in groups onto new tapes first, returns the disk operations of the partitions */
+pub fn merge_partitioned<R>(tapes: &mut [Tape<R>], output: &mut Tape<R>, partitions: usize, cmp: SharedCompare<R>) -> Result<usize, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr+Send, Standard: Distribution<R> {
+    let partitions = partitions.clamp(1, MAX_PARTITIONS);
+    let fan_in = fan_in(partitions);
+    if tapes.iter().filter(|tape| !tape.run_len.is_empty()).count() <= fan_in {
+        return merge_group(tapes, output, partitions, cmp);
+    }
+    let mut disk_ops = 0;
+    let mut merged = Vec::new();
+    let mut rest = tapes;
+    /* every group holds fan_in runs, tapes without a run go along with the group they are in */
+    while rest.iter().any(|tape| !tape.run_len.is_empty()) {
+        let mut len = 0;
+        let mut runs = 0;
+        while len < rest.len() && (runs < fan_in || rest[len].run_len.is_empty()) {
+            if !rest[len].run_len.is_empty() { runs+=1; }
+            len+=1;
+        }
+        let (group, tail) = rest.split_at_mut(len);
+        let mut tape = Tape::new();
+        disk_ops+=merge_group(group, &mut tape, partitions, cmp)?;
+        merged.push(tape);
+        rest = tail;
+    }
+    disk_ops+=merge_partitioned(&mut merged, output, partitions, cmp)?;
+    for tape in merged.iter_mut() { disk_ops+=tape.disk_ops() }
+    return Ok(disk_ops);
+}
+
+/* merge_partitioned of at most fan_in(partitions) tapes */
+fn merge_group<R>(tapes: &mut [Tape<R>], output: &mut Tape<R>, partitions: usize, cmp: SharedCompare<R>) -> Result<usize, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr+Send, Standard: Distribution<R> {
+    let mut disk_ops = 0;
+    let runs: Vec<(usize, usize)> = tapes.iter_mut().enumerate()
+        .filter_map(|(i, tape)| tape.run_len.first().map(|len| (i, len)))
+        .collect();
+
+    /* every run is sampled at the same step, a sample stands for the records up to the next one */
+    let mut run_samples: Vec<Vec<(usize, R)>> = Vec::new();
+    let mut samples: Vec<(R, usize)> = Vec::new();
+    for &(i, len) in &runs {
+        let step = len.div_ceil(partitions*SAMPLES).max(1);
+        let mut taken = Vec::new();
+        for index in (0..len).step_by(step) {
+            let mut reader = tapes[i].run_reader_at(0, index)?;
+            let key = reader.view_record();
+            disk_ops+=reader.disk_ops();
+            samples.push((key, step.min(len - index)));
+            taken.push((index, key));
+        }
+        run_samples.push(taken);
+    }
+    /* splitters are the keys at every 1/partitions of the records */
+    samples.sort_by(|a, b| cmp(&a.0, &b.0));
+    let total: usize = samples.iter().map(|sample| sample.1).sum();
+    let mut splitters = Vec::new();
+    let mut seen = 0;
+    for (key, weight) in samples {
+        if splitters.len() + 1 < partitions && seen >= total*(splitters.len() + 1)/partitions {
+            splitters.push(key);
+        }
+        seen+=weight;
+    }
+
+    /* bounds[run][p] is the first record of partition p in the run */
+    let mut bounds = Vec::new();
+    for (run, &(i, len)) in runs.iter().enumerate() {
+        let mut run_bounds = vec![0];
+        for splitter in &splitters {
+            let (index, ops) = lower_bound(&mut tapes[i], &run_samples[run], len, splitter, cmp)?;
+            run_bounds.push(index);
+            disk_ops+=ops;
+        }
+        run_bounds.push(len);
+        bounds.push(run_bounds);
+    }
+    let mut inputs = Vec::new();
+    for p in 0..splitters.len() + 1 {
+        let mut readers = Vec::new();
+        for (run, &(i, _)) in runs.iter().enumerate() {
+            readers.push((tapes[i].run_reader_at(0, bounds[run][p])?, bounds[run][p + 1] - bounds[run][p]));
+        }
+        inputs.push(readers);
+    }
+
+    let mut parts: Vec<Tape<R>> = inputs.iter().map(|_| Tape::new()).collect();
+    thread::scope(|scope| {
+        for (readers, part) in inputs.iter_mut().zip(parts.iter_mut()) {
+            scope.spawn(move || {
+                merge_run(readers, part, cmp);
+                part.flush().expect("cannot flush tape");
+            });
+        }
+    });
+    for readers in &inputs {
+        for (reader, _) in readers { disk_ops+=reader.disk_ops(); }
+    }
+
+    let mut merged = 0;
+    for part in parts.iter_mut() {
+        /* a partition can be empty, and an empty tape only finds out it is empty by reading */
+        for _i in 0..part.record_count() {
+            output.push(part.next_record());
+            merged+=1;
+        }
+        disk_ops+=part.disk_ops();
+    }
+    output.run_len.push(merged);
+    output.flush()?;
+    return Ok(disk_ops);
+}
+
+/* index of the first record of the only run of tape that does not come before key, returns the disk operations too;
+   only the records between the two samples around key are read */
+fn lower_bound<R>(tape: &mut Tape<R>, samples: &[(usize, R)], len: usize, key: &R, cmp: SharedCompare<R>) -> Result<(usize, usize), Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let after = samples.partition_point(|(_, sample)| cmp(sample, key) == Ordering::Less);
+    if after == 0 { return Ok((0, 0)); }
+    let (mut index, _) = samples[after - 1];
+    let end = samples.get(after).map_or(len, |&(next, _)| next);
+    let mut reader = tape.run_reader_at(0, index)?;
+    while index < end && cmp(&reader.view_record(), key) == Ordering::Less {
+        reader.next_record();
+        index+=1;
+    }
+    return Ok((index, reader.disk_ops()));
+}
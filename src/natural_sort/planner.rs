@@ -0,0 +1,198 @@
+use std::{
+    io::Error,
+    fmt::{self, Display},
+    str::FromStr,
+    mem::size_of,
+    collections::BinaryHeap,
+    cmp::Reverse
+};
+use rand::distributions::{Distribution, Standard};
+use serde::{Serialize, Deserialize};
+
+use crate::file_handler::BLOCK_SIZE;
+
+use super::{tape::Tape, cost::Cost, Compare, SortInfo, Runs, RunState, DISPLAY_AFTER_RUN, copy_run, merge_run, merge_run_reduce, clear_tapes, print_info};
+use super::reduce::Reduce;
+
+pub struct MergeStep {
+    /* runs merged with their lengths */
+    pub inputs: Vec<(usize, usize)>,
+    pub output: usize,
+    pub records: usize,
+    pub pass: usize
+}
+
+/* order of merges for runs of uneven length, runs are numbered in the order they are created */
+pub struct MergePlan {
+    /* initial runs and the records in them */
+    pub runs: usize,
+    pub records: usize,
+    pub fan_in: usize,
+    pub steps: Vec<MergeStep>
+}
+
+impl MergePlan {
+    /* huffman-like: always merge the shortest runs, only the first merge may take fewer of them;
+       the lengths are read once, from then on only the heap holds the runs that are left */
+    pub fn new(run_len: impl IntoIterator<Item = usize>, fan_in: usize) -> Self {
+        let k = fan_in.max(2);
+        /* length, run and the pass that wrote it */
+        let mut heap: BinaryHeap<Reverse<(usize, usize, usize)>> = run_len.into_iter().enumerate()
+            .map(|(run, len)| Reverse((len, run, 0)))
+            .collect();
+        let initial = heap.len();
+        let records = heap.iter().map(|Reverse((len, _, _))| len).sum();
+        let mut steps = Vec::new();
+
+        let mut first = if initial > 1 { (initial - 2)%(k - 1) + 2 } else { 0 };
+        while heap.len() > 1 {
+            let take = if first != 0 { first } else { k };
+            first = 0;
+            let mut inputs = Vec::new();
+            let mut pass = 0;
+            while inputs.len() < take {
+                match heap.pop() {
+                    Some(Reverse((len, run, written))) => {
+                        inputs.push((run, len));
+                        pass = pass.max(written);
+                    }
+                    None => break
+                }
+            }
+            let output = initial + steps.len();
+            let records = inputs.iter().map(|&(_, len)| len).sum();
+            heap.push(Reverse((records, output, pass + 1)));
+            steps.push(MergeStep { inputs, output, records, pass: pass + 1 });
+        }
+        return Self {
+            runs: initial,
+            records,
+            fan_in: k,
+            steps
+        }
+    }
+
+    pub fn passes(&self) -> usize {
+        return self.steps.iter().map(|step| step.pass).max().unwrap_or(0);
+    }
+    pub fn records_moved(&self) -> usize {
+        return self.steps.iter().map(|step| step.records).sum();
+    }
+    /* the first pass writes the runs once, every merge reads at least one page per input run */
+    pub fn cost(&self, b: f32) -> Cost {
+        let mut disk_ops = 2_f32*(self.records as f32)/b;
+        for step in &self.steps {
+            for &(_, len) in &step.inputs {
+                disk_ops += (len as f32/b).ceil().max(1_f32);
+            }
+            disk_ops += step.records as f32/b;
+        }
+        return Cost {
+            phases: self.passes() as f32,
+            disk_ops
+        }
+    }
+}
+
+impl Display for MergePlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "merge plan: {} runs, fan-in {}, {} passes, {} records moved",
+            self.runs, self.fan_in, self.passes(), self.records_moved())?;
+        for pass in 1..=self.passes() {
+            writeln!(f, "pass {pass}:")?;
+            for step in self.steps.iter().filter(|step| step.pass == pass) {
+                let inputs: Vec<String> = step.inputs.iter().map(|(run, _)| format!("r{run}")).collect();
+                writeln!(f, "  {} -> r{} ({} records)", inputs.join("+"), step.output, step.records)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/* lengths of the natural runs of a tape, the tape is rewound afterwards */
+pub fn detect_runs<R>(tape: &mut Tape<R>, cmp: Compare<R>) -> Result<Vec<usize>, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let mut run_len = Vec::new();
+    let mut state = RunState::Empty;
+    while !tape.is_empty() {
+        let record = tape.next_record();
+        if run_len.is_empty() || !state.extends(&record, cmp) {
+            run_len.push(0);
+            state = RunState::Empty;
+        }
+        state = state.push(record, cmp);
+        *run_len.last_mut().unwrap() += 1;
+    }
+    tape.flush()?;
+    return Ok(run_len);
+}
+
+/* runs are kept on one spool tape, every merge reads its inputs through separate readers */
+pub fn sort<R>(target: &mut [Tape<R>], fan_in: usize, cmp: Compare<R>, reduce: &mut Reduce<R>) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let mut disk_ops = 0;
+    let mut eliminated = 0;
+    target[0].print();
+
+    let mut runs = Runs::default();
+    let mut spool: Vec<Tape<R>> = vec![Tape::new()];
+    while !target[0].is_empty() {
+        copy_run(&mut target[0], &mut spool[0], &mut runs, cmp);
+    }
+    for tape in target.iter_mut() { disk_ops+=tape.disk_ops() }
+    clear_tapes(target);
+
+    let plan = MergePlan::new(spool[0].run_len.lens(), fan_in);
+    let display = DISPLAY_AFTER_RUN.lock().unwrap();
+    if *display {
+        print_info(&mut spool);
+        println!("{plan}");
+    }
+
+    if plan.runs == 1 {
+        let mut runs = vec![(spool[0].run_reader(0)?, plan.records)];
+        eliminated = merge_run_reduce(&mut runs, &mut target[0], cmp, reduce);
+        disk_ops+=runs[0].0.disk_ops();
+    }
+    for (i, step) in plan.steps.iter().enumerate() {
+        let mut runs = Vec::new();
+        for &(run, len) in &step.inputs {
+            runs.push((spool[0].run_reader(run)?, len));
+        }
+        /* the last merge goes straight onto the target tape */
+        if i + 1 == plan.steps.len() {
+            eliminated = merge_run_reduce(&mut runs, &mut target[0], cmp, reduce);
+        } else {
+            merge_run(&mut runs, &mut spool[0], cmp);
+        }
+        for (reader, _) in &runs { disk_ops+=reader.disk_ops() }
+    }
+    target[0].flush()?;
+    for tape in spool.iter_mut() { disk_ops+=tape.disk_ops() }
+    for tape in target.iter_mut() { disk_ops+=tape.disk_ops() }
+    if *display {
+        print_info(target);
+    }
+    println!();println!();
+    target[0].print();
+
+    let b: f32 = (BLOCK_SIZE/size_of::<R>()) as f32;
+    let teor = plan.cost(b);
+    return Ok(SortInfo {
+        number_of_phases: plan.passes().max(1),
+        disk_ops,
+
+        teor_number_of_phases: teor.phases,
+        teor_disk_ops: teor.disk_ops,
+
+        ascending_runs: runs.ascending,
+        descending_runs: runs.descending,
+        buckets: 0,
+
+        comparisons: 0,
+        prefix_hits: 0,
+        eliminated,
+
+        in_memory: false
+    })
+}
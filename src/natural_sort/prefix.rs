@@ -0,0 +1,137 @@
+use std::{
+    io::Error,
+    cmp::Ordering,
+    cell::Cell,
+    fmt::{self, Display},
+    str::FromStr
+};
+use rand::{Rng, distributions::{Distribution, Standard}};
+use serde::{Serialize, Deserialize};
+use serde_derive::{Serialize, Deserialize};
+
+use super::{tape::Tape, Compare, SortInfo, clear_tapes};
+use super::reduce::{Reduce, Writer, identical_order};
+
+/* bytes of the normalized key stored in front of every record */
+pub const PREFIX_LEN: usize = 8;
+
+/* sort order written as bytes: comparing two encodings with memcmp gives the same order as comparing the records */
+pub trait NormalizedKey<R> {
+    /* length of every encoding, keys longer than PREFIX_LEN are cut and ties go back to the comparator */
+    fn key_len(&self) -> usize;
+    /* false when different keys may be encoded the same way */
+    fn is_exact(&self) -> bool { return true; }
+    fn encode(&self, record: &R, out: &mut Vec<u8>);
+}
+
+/* record with the first PREFIX_LEN bytes of its normalized key */
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Prefixed<R> {
+    pub key: [u8; PREFIX_LEN],
+    pub record: R
+}
+
+impl<R: PartialOrd> PartialOrd for Prefixed<R> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return match self.key.cmp(&other.key) {
+            Ordering::Equal => self.record.partial_cmp(&other.record),
+            ordering => Some(ordering)
+        }
+    }
+}
+impl<R: PartialEq> PartialEq for Prefixed<R> {
+    fn eq(&self, other: &Self) -> bool {
+        return self.key == other.key && self.record == other.record;
+    }
+}
+
+/* the key is never shown, it only lives for the duration of the sort */
+impl<R: Display> Display for Prefixed<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.record)
+    }
+}
+impl<R: FromStr> FromStr for Prefixed<R> {
+    type Err = R::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self{ key: [0; PREFIX_LEN], record: s.parse()? })
+    }
+}
+impl<R> Distribution<Prefixed<R>> for Standard
+where Standard: Distribution<R> {
+    fn sample<G: Rng + ?Sized>(&self, rng: &mut G) -> Prefixed<R> {
+        return Prefixed{ key: [0; PREFIX_LEN], record: rng.gen() };
+    }
+}
+
+/* big endian, signed numbers have the sign bit flipped so negative ones come first */
+pub fn encode_i32(value: i32, out: &mut Vec<u8>) {
+    out.extend_from_slice(&((value as u32) ^ (1 << 31)).to_be_bytes());
+}
+pub fn encode_u64(value: u64, out: &mut Vec<u8>) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+/* negative floats have every bit flipped, positive ones only the sign bit, -0.0 counts as 0.0 */
+pub fn encode_f64(value: f64, out: &mut Vec<u8>) {
+    let value = if value == 0.0 { 0.0 } else { value };
+    let bits = value.to_bits();
+    let bits = if bits >> 63 == 1 { !bits } else { bits ^ (1 << 63) };
+    out.extend_from_slice(&bits.to_be_bytes());
+}
+
+/* memcmp on the prefixes first, cmp only when the prefixes are equal and do not hold the whole key */
+fn prefixed_order<'c, R>(cmp: Compare<'c, R>, complete: bool, hits: &'c Cell<usize>) -> impl Fn(&Prefixed<R>, &Prefixed<R>) -> Ordering + 'c {
+    return move |a, b| match a.key.cmp(&b.key) {
+        Ordering::Equal if !complete => cmp(&a.record, &b.record),
+        ordering => {
+            hits.set(hits.get() + 1);
+            ordering
+        }
+    }
+}
+
+/* computes the key prefix of every record of target[0] once, sorts with strategy and strips the prefixes,
+   reduce is applied while stripping */
+pub fn sort<R, K, F>(target: &mut [Tape<R>], key: &K, cmp: Compare<R>, reduce: &mut Reduce<R>, strategy: F) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R>,
+      K: NormalizedKey<R>,
+      F: FnOnce(&mut [Tape<Prefixed<R>>], Compare<Prefixed<R>>) -> Result<SortInfo, Error> {
+    let mut prefixed: Vec<Tape<Prefixed<R>>> = vec![Tape::<Prefixed<R>>::new()];
+    let mut encoded = Vec::with_capacity(key.key_len());
+    while !target[0].is_empty() {
+        let record = target[0].next_record();
+        encoded.clear();
+        key.encode(&record, &mut encoded);
+        encoded.resize(encoded.len().max(PREFIX_LEN), 0);
+        let mut prefix = [0; PREFIX_LEN];
+        prefix.copy_from_slice(&encoded[..PREFIX_LEN]);
+        prefixed[0].push(Prefixed{ key: prefix, record });
+    }
+    prefixed[0].flush()?;
+    let mut disk_ops = target[0].disk_ops();
+    clear_tapes(target);
+
+    /* identical records are brought together by a tie-break on the whole record, the prefix never holds it */
+    let drop_identical = matches!(reduce, Reduce::DropIdentical);
+    let identical = identical_order(cmp);
+    let cmp: Compare<R> = if drop_identical { &identical } else { cmp };
+    let hits = Cell::new(0);
+    let prefixed_cmp = prefixed_order(cmp, !drop_identical && key.key_len() <= PREFIX_LEN && key.is_exact(), &hits);
+    let mut info = strategy(&mut prefixed, &prefixed_cmp)?;
+
+    let sorted_ops = prefixed[0].disk_ops();
+    let mut writer = Writer::new(reduce, cmp);
+    while !prefixed[0].is_empty() {
+        writer.push(prefixed[0].next_record().record, &mut target[0]);
+    }
+    writer.finish(&mut target[0]);
+    target[0].run_len.set_single(writer.written);
+    target[0].flush()?;
+    disk_ops += prefixed[0].disk_ops() - sorted_ops + target[0].disk_ops();
+
+    info.disk_ops += disk_ops;
+    info.prefix_hits = hits.get();
+    info.eliminated += writer.eliminated;
+    return Ok(info);
+}
@@ -0,0 +1,156 @@
+use std::{
+    io::{Error, ErrorKind, BufWriter, Write},
+    cmp::Ordering,
+    cell::Cell,
+    fmt::{self, Display},
+    str::FromStr,
+    fs,
+    path::Path,
+    mem::size_of
+};
+use rand::distributions::{Distribution, Standard};
+use serde::{Serialize, Deserialize};
+
+use crate::file_handler::BLOCK_SIZE;
+
+use super::{tape::{Tape, MAX_OPEN_TAPES}, cost, Compare, SortInfo, smallest};
+
+/* first record of an input that comes before the record in front of it */
+#[derive(Debug)]
+pub struct Violation {
+    pub input: usize,
+    /* counted from 1, the line of a text file without empty lines */
+    pub position: usize,
+    pub previous: String,
+    pub record: String
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "input {} is not sorted: record {} {} comes after {}", self.input, self.position, self.record, self.previous)
+    }
+}
+impl std::error::Error for Violation {}
+
+/* one k-way merge of inputs that are each sorted by cmp, every input is checked while it is read,
+   the first record out of order stops the merge with an InvalidData error holding a Violation */
+pub fn merge_sorted<R>(inputs: &mut [Tape<R>], target: &mut Tape<R>, cmp: Compare<R>) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    if inputs.len() + 1 > MAX_OPEN_TAPES {
+        return Err(Error::other(format!("merging {} inputs keeps {} files open, budget is {MAX_OPEN_TAPES}", inputs.len(), inputs.len() + 1)));
+    }
+    let comparisons = Cell::new(0);
+    let counted = |a: &R, b: &R| {
+        comparisons.set(comparisons.get() + 1);
+        cmp(a, b)
+    };
+
+    let mut input_len = Vec::new();
+    for input in inputs.iter_mut() {
+        input.flush()?;
+        input_len.push(input.record_count());
+    }
+    let before = inputs.iter_mut().map(|input| input.disk_ops()).sum::<usize>() + target.disk_ops();
+    let mut runs: Vec<(&mut Tape<R>, usize)> = inputs.iter_mut().zip(input_len.iter().copied()).collect();
+    let mut last: Vec<Option<R>> = vec![None; runs.len()];
+    let mut merged = 0;
+    while let Some(i) = smallest(&mut runs, &counted) {
+        let record = runs[i].0.next_record();
+        runs[i].1 -= 1;
+        if let Some(previous) = last[i].filter(|previous| counted(&record, previous) == Ordering::Less) {
+            target.clear()?;
+            return Err(Error::new(ErrorKind::InvalidData, Violation {
+                input: i,
+                position: input_len[i] - runs[i].1,
+                previous: previous.to_string(),
+                record: record.to_string()
+            }));
+        }
+        last[i] = Some(record);
+        target.push(record);
+        merged+=1;
+    }
+    target.run_len.set_single(merged);
+    target.flush()?;
+
+    let mut disk_ops = target.disk_ops();
+    for input in inputs.iter_mut() {
+        input.flush()?;
+        disk_ops+=input.disk_ops();
+    }
+    disk_ops-=before;
+    let b: f32 = (BLOCK_SIZE/size_of::<R>()) as f32;
+    let teor = cost::merge_sorted(&input_len, b);
+    return Ok(SortInfo {
+        number_of_phases: 1,
+        disk_ops,
+
+        teor_number_of_phases: teor.phases,
+        teor_disk_ops: teor.disk_ops,
+
+        ascending_runs: inputs.len(),
+        descending_runs: 0,
+        buckets: 0,
+
+        comparisons: comparisons.get(),
+        prefix_hits: 0,
+        eliminated: 0,
+
+        in_memory: false
+    })
+}
+
+/* one record per line, empty lines are skipped */
+pub fn load<R>(path: &Path) -> Result<Tape<R>, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let mut tape = Tape::new();
+    let data = fs::read_to_string(path)?;
+    for (line, record) in data.lines().enumerate() {
+        if record.trim().is_empty() { continue; }
+        match record.parse() {
+            Ok(record) => tape.push(record),
+            Err(_) => return Err(Error::new(ErrorKind::InvalidData, format!("{}:{}: wrong format", path.display(), line + 1)))
+        }
+    }
+    tape.flush()?;
+    return Ok(tape);
+}
+
+/* the text form of a record that load reads back, Display is only meant to be read by people */
+pub trait WriteRecord {
+    fn write_record(&self, out: &mut dyn Write) -> Result<(), Error>;
+}
+
+/* writes the records one per line in the form load reads back, returns how many there were */
+pub fn write_lines<R, I>(records: I, path: &Path) -> Result<usize, Error>
+where R: WriteRecord, I: IntoIterator<Item = Result<R, Error>> {
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    let mut written = 0;
+    for record in records {
+        record?.write_record(&mut file)?;
+        writeln!(file)?;
+        written+=1;
+    }
+    file.flush()?;
+    return Ok(written);
+}
+
+/* writes every record of the tape as one line with write_lines and rewinds the tape */
+pub fn save<R>(tape: &mut Tape<R>, path: &Path) -> Result<usize, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+WriteRecord, Standard: Distribution<R> {
+    tape.flush()?;
+    let records = write_lines(tape.iter(), path)?;
+    tape.rewind()?;
+    return Ok(records);
+}
+
+/* merge_sorted of text files, every file is loaded onto its own tape first */
+pub fn merge_files<R, P>(paths: &[P], target: &mut Tape<R>, cmp: Compare<R>) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R>,
+      P: AsRef<Path> {
+    let mut inputs = Vec::new();
+    for path in paths {
+        inputs.push(load(path.as_ref())?);
+    }
+    return merge_sorted(&mut inputs, target, cmp);
+}
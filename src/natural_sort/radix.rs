@@ -0,0 +1,110 @@
+use std::{
+    io::Error,
+    fmt::Display,
+    str::FromStr,
+    mem::size_of
+};
+use rand::distributions::{Distribution, Standard};
+use serde::{Serialize, Deserialize};
+use cute::c;
+
+use crate::file_handler::BLOCK_SIZE;
+
+use super::{tape::{Tape, MAX_OPEN_TAPES}, cost, SortInfo, DISPLAY_AFTER_RUN, clear_tapes, print_info};
+
+/* widest digit whose buckets and the target tape can be open at once */
+pub const MAX_DIGIT_BITS: u32 = usize::BITS - 1 - (MAX_OPEN_TAPES - 1).leading_zeros();
+
+/* sort order written as an unsigned number: comparing two keys as numbers gives the same order as comparing the records */
+pub trait RadixKey<R> {
+    /* every key is below 2^key_bits */
+    fn key_bits(&self) -> u32;
+    fn key(&self, record: &R) -> u128;
+}
+
+/* LSD radix sort of target[0]: every pass distributes the records by one digit_bits wide digit of their key
+   onto 2^digit_bits bucket tapes and copies the buckets back in order, starting from the lowest digit;
+   the number of passes depends only on how many low bits of the keys differ and records with equal keys keep their input order */
+pub fn sort<R, K>(target: &mut [Tape<R>], key: &K, digit_bits: u32) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R>,
+      K: RadixKey<R> {
+    if !(1..=MAX_DIGIT_BITS).contains(&digit_bits) {
+        return Err(Error::other(format!("digits of {digit_bits} bits need {} bucket tapes, digits can have 1 to {MAX_DIGIT_BITS} bits", 1_u64 << digit_bits.min(63))));
+    }
+    let records = target[0].record_count();
+    /* an empty tape is already sorted and has no keys to trim the passes by */
+    let mut passes = if records == 0 { 0 } else { key.key_bits().div_ceil(digit_bits) as usize };
+    let mut min_key = u128::MAX;
+    let mut max_key = 0;
+    let mask = (1_u128 << digit_bits) - 1;
+    target[0].print();
+
+    let mut disk_ops = 0;
+    let mut buckets: Vec<Tape<R>> = c![Tape::new(), for _i in 0..1 << digit_bits];
+    let mut pass = 0;
+    while pass < passes {
+        let shift = pass as u32*digit_bits;
+        let mut lens = vec![0; buckets.len()];
+        /* an empty tape only finds out it is empty by reading, so the record count is used */
+        for _i in 0..records {
+            let record = target[0].next_record();
+            let record_key = key.key(&record);
+            if pass == 0 {
+                min_key = min_key.min(record_key);
+                max_key = max_key.max(record_key);
+            }
+            let digit = ((record_key >> shift) & mask) as usize;
+            buckets[digit].push(record);
+            lens[digit]+=1;
+        }
+        for tape in target.iter_mut() { disk_ops+=tape.disk_ops() }
+        clear_tapes(target);
+
+        for (bucket, &len) in buckets.iter_mut().zip(&lens) {
+            bucket.run_len.set_single(len);
+            bucket.flush()?;
+        }
+        let display = DISPLAY_AFTER_RUN.lock().unwrap();
+        if *display {
+            print_info(&mut buckets);
+            println!();println!();
+        }
+        drop(display);
+
+        for (bucket, len) in buckets.iter_mut().zip(lens) {
+            for _i in 0..len { target[0].push(bucket.next_record()); }
+            disk_ops+=bucket.disk_ops();
+        }
+        clear_tapes(&mut buckets);
+        target[0].run_len.set_single(records);
+        target[0].flush()?;
+        if pass == 0 {
+            /* digits above the highest bit where the keys differ are the same in every record, their passes would not move anything */
+            passes = (u128::BITS - (min_key ^ max_key).leading_zeros()).div_ceil(digit_bits).max(1) as usize;
+        }
+        pass+=1;
+    }
+    disk_ops+=target[0].disk_ops();
+    println!();println!();
+    target[0].print();
+
+    let b: f32 = (BLOCK_SIZE/size_of::<R>()) as f32;
+    let teor = cost::radix(passes, 1 << digit_bits, records, b);
+    return Ok(SortInfo {
+        number_of_phases: passes,
+        disk_ops,
+
+        teor_number_of_phases: teor.phases,
+        teor_disk_ops: teor.disk_ops,
+
+        ascending_runs: 0,
+        descending_runs: 0,
+        buckets: 0,
+
+        comparisons: 0,
+        prefix_hits: 0,
+        eliminated: 0,
+
+        in_memory: false
+    })
+}
@@ -0,0 +1,201 @@
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display}
+};
+use rand::{Rng, distributions::{Distribution, Standard}};
+use serde::{Serialize, Deserialize};
+use serde_derive::{Serialize, Deserialize};
+
+use super::{tape::Tape, Compare};
+
+/* folds records with equal keys into the first one of their group */
+pub trait Reducer<R> {
+    fn start(&mut self, _first: &R) {}
+    fn combine(&mut self, kept: &mut R, next: &R);
+    /* after the last record */
+    fn finish(&mut self) {}
+}
+
+/* what the final merge does with records that compare equal */
+pub enum Reduce<'r, R> {
+    Keep,
+    /* records with equal keys, only the first one is written */
+    DropDuplicates,
+    /* records with the same bytes, records with equal keys but different contents are all written;
+       the sort breaks ties with identical_order so identical records are next to each other */
+    DropIdentical,
+    Combine(&'r mut dyn Reducer<R>)
+}
+
+/* records with the same bytes */
+fn identical<R: Serialize>(a: &R, b: &R) -> bool {
+    return bincode::serialize(a).ok() == bincode::serialize(b).ok();
+}
+
+/* cmp with ties broken by the bytes of the records, only identical records are equal */
+pub fn identical_order<'c, R: Serialize>(cmp: Compare<'c, R>) -> impl Fn(&R, &R) -> Ordering + 'c {
+    return move |a, b| cmp(a, b).then_with(|| {
+        let bytes = |record: &R| bincode::serialize(record).expect("Problem serializing record");
+        bytes(a).cmp(&bytes(b))
+    });
+}
+
+/* the first record of every group, its contents are never changed */
+pub struct KeepFirst;
+
+impl<R> Reducer<R> for KeepFirst {
+    fn combine(&mut self, _kept: &mut R, _next: &R) {}
+}
+
+/* first record of a group with what was aggregated over it */
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Group<R, V> {
+    pub first: R,
+    pub value: V
+}
+
+impl<R: PartialOrd, V> PartialOrd for Group<R, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return self.first.partial_cmp(&other.first);
+    }
+}
+impl<R: PartialEq, V> PartialEq for Group<R, V> {
+    fn eq(&self, other: &Self) -> bool {
+        return self.first == other.first;
+    }
+}
+impl<R: Display, V: Display> Display for Group<R, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.first, self.value)
+    }
+}
+impl<R, V: Default> Distribution<Group<R, V>> for Standard
+where Standard: Distribution<R> {
+    fn sample<G: Rng + ?Sized>(&self, rng: &mut G) -> Group<R, V> {
+        return Group{ first: rng.gen(), value: V::default() };
+    }
+}
+
+/* number of records in every group, groups are written onto a tape as soon as the next one starts */
+pub struct Count<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    pub groups: Tape<Group<R, usize>>,
+    current: Option<Group<R, usize>>
+}
+
+impl<R> Count<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    pub fn new() -> Self {
+        return Self { groups: Tape::<Group<R, usize>>::new(), current: None };
+    }
+}
+
+impl<R> Reducer<R> for Count<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy, Standard: Distribution<R> {
+    fn start(&mut self, first: &R) {
+        if let Some(group) = self.current.replace(Group{ first: *first, value: 1 }) {
+            self.groups.push(group);
+        }
+    }
+    fn combine(&mut self, _kept: &mut R, _next: &R) {
+        if let Some(group) = &mut self.current { group.value+=1; }
+    }
+    fn finish(&mut self) {
+        if let Some(group) = self.current.take() { self.groups.push(group); }
+        self.groups.flush().expect("cannot flush tape");
+    }
+}
+
+/* sum of value over every group, written like Count */
+pub struct Sum<R, F: Fn(&R) -> f64>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    value: F,
+    pub groups: Tape<Group<R, f64>>,
+    current: Option<Group<R, f64>>
+}
+
+impl<R, F: Fn(&R) -> f64> Sum<R, F>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    pub fn new(value: F) -> Self {
+        return Self { value, groups: Tape::<Group<R, f64>>::new(), current: None };
+    }
+}
+
+impl<R, F: Fn(&R) -> f64> Reducer<R> for Sum<R, F>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy, Standard: Distribution<R> {
+    fn start(&mut self, first: &R) {
+        if let Some(group) = self.current.replace(Group{ first: *first, value: (self.value)(first) }) {
+            self.groups.push(group);
+        }
+    }
+    fn combine(&mut self, _kept: &mut R, next: &R) {
+        let value = (self.value)(next);
+        if let Some(group) = &mut self.current { group.value+=value; }
+    }
+    fn finish(&mut self) {
+        if let Some(group) = self.current.take() { self.groups.push(group); }
+        self.groups.flush().expect("cannot flush tape");
+    }
+}
+
+/* writes sorted records onto a tape, the last record waits until the next one shows it is not a duplicate */
+pub(super) struct Writer<'w, 'r, R> {
+    reduce: &'w mut Reduce<'r, R>,
+    cmp: Compare<'w, R>,
+    pending: Option<R>,
+    pub written: usize,
+    pub eliminated: usize
+}
+
+impl<'w, 'r, R> Writer<'w, 'r, R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy, Standard: Distribution<R> {
+    pub fn new(reduce: &'w mut Reduce<'r, R>, cmp: Compare<'w, R>) -> Self {
+        return Self {
+            reduce,
+            cmp,
+            pending: None,
+            written: 0,
+            eliminated: 0
+        }
+    }
+
+    pub fn push(&mut self, record: R, output: &mut Tape<R>) {
+        let same_key = self.pending.as_ref().is_some_and(|pending| (self.cmp)(&record, pending) == Ordering::Equal);
+        match &mut self.reduce {
+            Reduce::Keep => {
+                output.push(record);
+                self.written+=1;
+                return;
+            }
+            Reduce::DropDuplicates if same_key => {
+                self.eliminated+=1;
+                return;
+            }
+            /* identical records are next to each other, so only the previous one can be the same */
+            Reduce::DropIdentical if same_key && self.pending.as_ref().is_some_and(|pending| identical(pending, &record)) => {
+                self.eliminated+=1;
+                return;
+            }
+            Reduce::Combine(reducer) => {
+                if same_key {
+                    reducer.combine(self.pending.as_mut().unwrap(), &record);
+                    self.eliminated+=1;
+                    return;
+                }
+                reducer.start(&record);
+            }
+            Reduce::DropDuplicates | Reduce::DropIdentical => {}
+        }
+        if let Some(pending) = self.pending.replace(record) {
+            output.push(pending);
+        }
+        self.written+=1;
+    }
+
+    pub fn finish(&mut self, output: &mut Tape<R>) {
+        if let Some(pending) = self.pending.take() {
+            output.push(pending);
+        }
+        if let Reduce::Combine(reducer) = &mut self.reduce { reducer.finish(); }
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write, Seek, SeekFrom},
+    path::PathBuf,
+    mem::size_of
+};
+
+use crate::file_handler::BLOCK_SIZE;
+
+/* entries of one page of the index */
+const PAGE_ENTRIES: usize = BLOCK_SIZE/size_of::<u64>();
+
+/* run lengths of a tape: for every run the number of records up to its end, so the length and the offset of a run
+   are one lookup each; full pages go to a file next to the tape, only the page being written and the page
+   read last are kept in memory; every page read or written counts as a disk operation of the tape */
+pub struct RunIndex {
+    path: PathBuf,
+    /* created when the first page is full */
+    file: Option<File>,
+    /* entries saved in the file, always whole pages */
+    on_disk: usize,
+    /* entries after the saved ones, at most a page and one */
+    tail: Vec<usize>,
+    /* page number and entries of the page read last */
+    cache: Option<(usize, Vec<usize>)>,
+    /* empty runs in front of the stored ones */
+    empty_front: usize,
+    /* pages read and written since the tape was cleared */
+    disk_ops: usize
+}
+
+impl RunIndex {
+    pub fn new(path: PathBuf) -> Self {
+        return Self {
+            path,
+            file: None,
+            on_disk: 0,
+            tail: Vec::new(),
+            cache: None,
+            empty_front: 0,
+            disk_ops: 0
+        }
+    }
+
+    pub fn len(&self) -> usize { return self.empty_front + self.on_disk + self.tail.len(); }
+    pub fn is_empty(&self) -> bool { return self.len() == 0; }
+
+    pub fn push(&mut self, len: usize) {
+        let end = self.records() + len;
+        self.tail.push(end);
+        /* the last entry stays in memory, runs grow at the end */
+        if self.tail.len() > PAGE_ENTRIES {
+            let page: Vec<usize> = self.tail.drain(..PAGE_ENTRIES).collect();
+            self.write_page(&page).expect("cannot write run index");
+            self.on_disk+=PAGE_ENTRIES;
+        }
+    }
+    pub fn pop(&mut self) -> Option<usize> {
+        if self.tail.is_empty() {
+            if self.empty_front == 0 { return None; }
+            self.empty_front-=1;
+            return Some(0);
+        }
+        let len = self.get(self.len() - 1);
+        self.tail.pop();
+        /* the last saved page comes back, so the tail keeps the last entry */
+        if self.tail.is_empty() && self.on_disk > 0 {
+            self.on_disk-=PAGE_ENTRIES;
+            self.tail = self.read_page(self.on_disk/PAGE_ENTRIES).expect("cannot read run index");
+            self.cache = None;
+            if let Some(file) = &self.file {
+                file.set_len((self.on_disk*size_of::<u64>()) as u64).expect("cannot write run index");
+            }
+        }
+        return len;
+    }
+    pub fn clear(&mut self) {
+        if let Some(file) = &self.file {
+            file.set_len(0).expect("cannot write run index");
+        }
+        self.on_disk = 0;
+        self.tail.clear();
+        self.cache = None;
+        self.empty_front = 0;
+    }
+    /* the tape holds a single run of len records */
+    pub fn set_single(&mut self, len: usize) {
+        self.clear();
+        self.push(len);
+    }
+    /* count empty runs in front of the first one */
+    pub fn insert_empty_front(&mut self, count: usize) {
+        self.empty_front+=count;
+    }
+
+    pub fn get(&mut self, run: usize) -> Option<usize> {
+        if run >= self.len() { return None; }
+        if run < self.empty_front { return Some(0); }
+        return Some(self.end(run - self.empty_front) - self.offset(run));
+    }
+    /* length of a run that has to exist */
+    pub fn at(&mut self, run: usize) -> usize {
+        return self.get(run).expect("run out of range");
+    }
+    pub fn first(&mut self) -> Option<usize> { return self.get(0); }
+    pub fn last(&mut self) -> Option<usize> {
+        if self.is_empty() { return None; }
+        return self.get(self.len() - 1);
+    }
+    /* records in front of the run */
+    pub fn offset(&mut self, run: usize) -> usize {
+        if run <= self.empty_front { return 0; }
+        return self.end(run - self.empty_front - 1);
+    }
+    /* records in every run */
+    pub fn records(&self) -> usize {
+        /* pages are saved with an entry left after them and pop reads the last page back, so the tail is only empty with no pages */
+        return self.tail.last().copied().unwrap_or(0);
+    }
+    pub fn lens(&mut self) -> impl Iterator<Item = usize> + '_ {
+        return (0..self.len()).map(|run| self.at(run));
+    }
+    pub fn disk_ops(&self) -> usize { return self.disk_ops; }
+    /* the tape counts its disk operations from zero again */
+    pub fn reset_disk_ops(&mut self) {
+        self.disk_ops = 0;
+    }
+
+    /* private methods */
+    /* end of the i-th stored run */
+    fn end(&mut self, i: usize) -> usize {
+        if i >= self.on_disk { return self.tail[i - self.on_disk]; }
+        let page = i/PAGE_ENTRIES;
+        if self.cache.as_ref().is_none_or(|(cached, _)| *cached != page) {
+            let entries = self.read_page(page).expect("cannot read run index");
+            self.cache = Some((page, entries));
+        }
+        return self.cache.as_ref().map_or(0, |(_, entries)| entries[i%PAGE_ENTRIES]);
+    }
+    fn read_page(&mut self, page: usize) -> io::Result<Vec<usize>> {
+        self.disk_ops+=1;
+        let mut bytes = vec![0; PAGE_ENTRIES*size_of::<u64>()];
+        let mut file = self.file.as_ref().ok_or(io::Error::other("run index without file"))?;
+        file.seek(SeekFrom::Start((page*bytes.len()) as u64))?;
+        file.read_exact(&mut bytes)?;
+        return Ok(bytes.chunks_exact(size_of::<u64>())
+            .map(|entry| u64::from_le_bytes(entry.try_into().unwrap()) as usize)
+            .collect());
+    }
+    fn write_page(&mut self, entries: &[usize]) -> io::Result<()> {
+        self.disk_ops+=1;
+        if self.file.is_none() {
+            self.file = Some(File::options().read(true).write(true).create(true).truncate(true).open(&self.path)?);
+        }
+        let bytes: Vec<u8> = entries.iter().flat_map(|&end| (end as u64).to_le_bytes()).collect();
+        let mut file = self.file.as_ref().unwrap();
+        file.seek(SeekFrom::Start((self.on_disk*size_of::<u64>()) as u64))?;
+        file.write_all(&bytes)?;
+        return Ok(());
+    }
+}
+
+impl Drop for RunIndex {
+    fn drop(&mut self) {
+        if self.file.is_some() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
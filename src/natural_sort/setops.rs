@@ -0,0 +1,180 @@
+use std::{
+    io::Error,
+    cmp::Ordering,
+    fmt::{self, Display},
+    str::FromStr
+};
+use rand::distributions::{Distribution, Standard};
+use serde::{Serialize, Deserialize};
+
+use super::{tape::Tape, cost::Algorithm, Compare, SortInfo, sort_if_needed};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SetOp {
+    Union,
+    Intersection,
+    /* keys of the left input missing from the right one */
+    Difference,
+    SymmetricDifference
+}
+
+impl SetOp {
+    /* whether a key found only on the left, only on the right or on both sides is written */
+    fn keeps(&self, left: bool, right: bool) -> bool {
+        return match self {
+            SetOp::Union => true,
+            SetOp::Intersection => left && right,
+            SetOp::Difference => left && !right,
+            SetOp::SymmetricDifference => left != right
+        }
+    }
+}
+
+impl Display for SetOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SetOp::Union => "union",
+            SetOp::Intersection => "intersect",
+            SetOp::Difference => "difference",
+            SetOp::SymmetricDifference => "symdiff",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for SetOp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "union" => Ok(SetOp::Union),
+            "intersect" => Ok(SetOp::Intersection),
+            "difference" => Ok(SetOp::Difference),
+            "symdiff" => Ok(SetOp::SymmetricDifference),
+            _ => Err(format!("unknown set operation: {s}"))
+        }
+    }
+}
+
+pub struct SetInfo {
+    /* None when the input was already sorted by the key */
+    pub left_sort: Option<SortInfo>,
+    pub right_sort: Option<SortInfo>,
+
+    /* distinct keys of every input and keys found in both */
+    pub left_keys: usize,
+    pub right_keys: usize,
+    pub common_keys: usize,
+    /* records written, one per key */
+    pub written: usize,
+    /* records with a key already seen on the same input */
+    pub duplicates: usize,
+    pub disk_ops: usize
+}
+
+/* merges left[0] and right[0] group by group, records are equal when cmp says so and every key is written once,
+   taken from the left input when both have it; inputs not sorted by cmp are sorted with algorithm and n tapes first */
+pub fn apply<R>(op: SetOp, left: &mut [Tape<R>], right: &mut [Tape<R>], output: &mut Tape<R>,
+                cmp: Compare<R>, algorithm: Algorithm, n: usize) -> Result<SetInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let left_sort = sort_if_needed(left, cmp, algorithm, n)?;
+    let right_sort = sort_if_needed(right, cmp, algorithm, n)?;
+    let before = left[0].disk_ops() + right[0].disk_ops() + output.disk_ops();
+
+    let mut info = SetInfo {
+        left_sort,
+        right_sort,
+
+        left_keys: 0,
+        right_keys: 0,
+        common_keys: 0,
+        written: 0,
+        duplicates: 0,
+        disk_ops: 0
+    };
+    /* an empty tape only finds out it is empty by reading, so both sides are read by their record counts */
+    let (mut left_left, mut right_left) = (left[0].record_count(), right[0].record_count());
+    loop {
+        let ordering = match (left_left == 0, right_left == 0) {
+            (true, true) => break,
+            (false, true) => Ordering::Less,
+            (true, false) => Ordering::Greater,
+            (false, false) => cmp(&left[0].view_record(), &right[0].view_record())
+        };
+        let (in_left, in_right) = (ordering != Ordering::Greater, ordering != Ordering::Less);
+        let first = if in_left { left[0].view_record() } else { right[0].view_record() };
+        if in_left {
+            info.duplicates += skip_group(&mut left[0], &mut left_left, &first, cmp) - 1;
+            info.left_keys+=1;
+        }
+        if in_right {
+            info.duplicates += skip_group(&mut right[0], &mut right_left, &first, cmp) - 1;
+            info.right_keys+=1;
+        }
+        if in_left && in_right { info.common_keys+=1; }
+        if op.keeps(in_left, in_right) {
+            output.push(first);
+            info.written+=1;
+        }
+    }
+    output.run_len.set_single(info.written);
+    output.flush()?;
+    left[0].flush()?;
+    right[0].flush()?;
+
+    info.disk_ops = left[0].disk_ops() + right[0].disk_ops() + output.disk_ops() - before;
+    return Ok(info);
+}
+
+/* reads every record equal to first out of the left ones still on the tape, returns how many there were */
+fn skip_group<R>(tape: &mut Tape<R>, left: &mut usize, first: &R, cmp: Compare<R>) -> usize
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let mut records = 0;
+    while *left > 0 && cmp(&tape.view_record(), first) == Ordering::Equal {
+        tape.next_record();
+        *left-=1;
+        records+=1;
+    }
+    return records;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::triangle::Triangle;
+
+    const RECORDS: [&str; 3] = ["{(0,0),(2,0),(0,2)}", "{(0,0),(1,0),(0,1)}", "{(0,0),(1,0),(0,1)}"];
+
+    fn tape(records: &[&str]) -> Vec<Tape<Triangle>> {
+        return vec![records.iter().map(|record| record.parse::<Triangle>().unwrap()).collect()];
+    }
+    /* doubled areas of the records written with one operand empty */
+    fn apply_empty(op: SetOp, empty_left: bool) -> Vec<u128> {
+        let (mut left, mut right) = if empty_left { (tape(&[]), tape(&RECORDS)) } else { (tape(&RECORDS), tape(&[])) };
+        let mut output = Tape::new();
+        let info = apply(op, &mut left, &mut right, &mut output, &Triangle::cmp, Algorithm::Natural, 3).unwrap();
+        assert_eq!(info.common_keys, 0);
+        output.rewind().unwrap();
+        let written: Vec<u128> = output.iter().map(|record| record.map(Triangle::doubled_area)).collect::<Result<_, _>>().unwrap();
+        assert_eq!(written.len(), info.written);
+        return written;
+    }
+
+    #[test]
+    fn union_with_empty() {
+        assert_eq!(apply_empty(SetOp::Union, true), [1, 4]);
+        assert_eq!(apply_empty(SetOp::Union, false), [1, 4]);
+    }
+
+    #[test]
+    fn intersect_with_empty() {
+        assert!(apply_empty(SetOp::Intersection, true).is_empty());
+        assert!(apply_empty(SetOp::Intersection, false).is_empty());
+    }
+
+    #[test]
+    fn difference_with_empty() {
+        assert!(apply_empty(SetOp::Difference, true).is_empty());
+        assert_eq!(apply_empty(SetOp::Difference, false), [1, 4]);
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    io::Error,
+    cell::Cell,
+    fmt::Display,
+    str::FromStr,
+    mem::{self, size_of},
+    vec
+};
+use rand::distributions::{Distribution, Standard};
+use serde::{Serialize, Deserialize};
+
+use crate::file_handler::BLOCK_SIZE;
+
+use super::{tape::Tape, cost::{self, Algorithm}, Compare, SortInfo, DISPLAY_AFTER_RUN, PRINT_TAPES, FORCE_EXTERNAL, memory_records, sort_with};
+
+/* where the sorted records are read from */
+enum Source<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    Memory(vec::IntoIter<R>),
+    Tape(Tape<R>)
+}
+
+/* sorted records read from memory or from the tape the sort left them on, one page at a time;
+   the tape file is removed when this is dropped, even before the last record */
+pub struct Sorted<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    source: Source<R>,
+    left: usize,
+    info: SortInfo
+}
+
+impl<R> Sorted<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    pub fn info(&self) -> &SortInfo { return &self.info; }
+}
+
+impl<R> Iterator for Sorted<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    type Item = R;
+
+    fn next(&mut self) -> Option<R> {
+        if self.left == 0 { return None; }
+        self.left-=1;
+        return match &mut self.source {
+            Source::Memory(records) => records.next(),
+            Source::Tape(tape) => Some(tape.next_record())
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        return (self.left, Some(self.left));
+    }
+}
+impl<R> ExactSizeIterator for Sorted<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {}
+
+/* sorts the records with algorithm and n tapes without printing them, records are buffered up to the memory budget
+   and only written onto a tape when there are more of them */
+pub fn sort_iter<R, I>(records: I, algorithm: Algorithm, n: usize, cmp: Compare<R>) -> Result<Sorted<R>, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R>,
+      I: IntoIterator<Item = R> {
+    let external = *FORCE_EXTERNAL.lock().unwrap();
+    let budget = if external { 0 } else { memory_records::<R>() };
+    let mut records = records.into_iter();
+    let buffer: Vec<R> = records.by_ref().take(budget + 1).collect();
+    if buffer.len() <= budget {
+        return Ok(sort_buffer(buffer, cmp));
+    }
+
+    let mut target: Vec<Tape<R>> = vec![buffer.into_iter().chain(records).collect()];
+    let print = mem::replace(&mut *PRINT_TAPES.lock().unwrap(), false);
+    let display = mem::replace(&mut *DISPLAY_AFTER_RUN.lock().unwrap(), false);
+    let info = sort_with(algorithm, &mut target, n, cmp);
+    *PRINT_TAPES.lock().unwrap() = print;
+    *DISPLAY_AFTER_RUN.lock().unwrap() = display;
+    let info = info?;
+    let tape = target.swap_remove(0);
+    return Ok(Sorted { left: tape.record_count(), source: Source::Tape(tape), info });
+}
+
+/* records that fit in memory never touch a tape */
+fn sort_buffer<R>(mut buffer: Vec<R>, cmp: Compare<R>) -> Sorted<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let comparisons = Cell::new(0);
+    buffer.sort_by(|a, b| {
+        comparisons.set(comparisons.get() + 1);
+        cmp(a, b)
+    });
+    let b: f32 = (BLOCK_SIZE/size_of::<R>()) as f32;
+    let teor = cost::in_memory(buffer.len(), b);
+    let info = SortInfo {
+        number_of_phases: 1,
+        disk_ops: 0,
+
+        teor_number_of_phases: teor.phases,
+        /* nothing is read from or written to a tape */
+        teor_disk_ops: 0_f32,
+
+        ascending_runs: 0,
+        descending_runs: 0,
+        buckets: 0,
+
+        comparisons: comparisons.get(),
+        prefix_hits: 0,
+        eliminated: 0,
+
+        in_memory: true
+    };
+    return Sorted { left: buffer.len(), source: Source::Memory(buffer.into_iter()), info };
+}
+
+/* sort_iter as a method of every iterator */
+pub trait SortExternal: Iterator + Sized
+where Self::Item: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<Self::Item> {
+    fn sort_external(self, algorithm: Algorithm, n: usize, cmp: Compare<Self::Item>) -> Result<Sorted<Self::Item>, Error> {
+        return sort_iter(self, algorithm, n, cmp);
+    }
+}
+
+impl<I: Iterator> SortExternal for I
+where I::Item: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<I::Item> {}
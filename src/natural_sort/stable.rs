@@ -0,0 +1,131 @@
+use std::{
+    io::Error,
+    cmp::Ordering,
+    fmt::{self, Display},
+    str::FromStr
+};
+use rand::{Rng, distributions::{Distribution, Standard}};
+use serde::{Serialize, Deserialize};
+use serde_derive::{Serialize, Deserialize};
+
+use super::{tape::Tape, Compare, SortInfo, clear_tapes};
+use super::reduce::{Reduce, Writer};
+
+/* record with its position in the input, equal records keep their input order */
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Stamped<R> {
+    pub record: R,
+    pub seq: u64
+}
+
+impl<R: PartialOrd> PartialOrd for Stamped<R> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return match self.record.partial_cmp(&other.record) {
+            Some(Ordering::Equal) => self.seq.partial_cmp(&other.seq),
+            ordering => ordering
+        }
+    }
+}
+impl<R: PartialEq> PartialEq for Stamped<R> {
+    fn eq(&self, other: &Self) -> bool {
+        return self.record == other.record && self.seq == other.seq;
+    }
+}
+
+/* the sequence number never shows up outside of the sort */
+impl<R: Display> Display for Stamped<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.record)
+    }
+}
+impl<R: FromStr> FromStr for Stamped<R> {
+    type Err = R::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self{ record: s.parse()?, seq: 0 })
+    }
+}
+impl<R> Distribution<Stamped<R>> for Standard
+where Standard: Distribution<R> {
+    fn sample<G: Rng + ?Sized>(&self, rng: &mut G) -> Stamped<R> {
+        return Stamped{ record: rng.gen(), seq: 0 };
+    }
+}
+
+/* cmp on the records, ties broken by input position */
+pub fn stamped_order<R>(cmp: Compare<'_, R>) -> impl Fn(&Stamped<R>, &Stamped<R>) -> Ordering + '_ {
+    return move |a, b| cmp(&a.record, &b.record).then(a.seq.cmp(&b.seq));
+}
+
+/* stamps every record of target[0] with its position, sorts with strategy and strips the stamps,
+   reduce is applied while stripping so the first record of a group is the first one of the input */
+pub fn sort<R, F>(target: &mut [Tape<R>], cmp: Compare<R>, reduce: &mut Reduce<R>, strategy: F) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R>,
+      F: FnOnce(&mut [Tape<Stamped<R>>]) -> Result<SortInfo, Error> {
+    let mut stamped: Vec<Tape<Stamped<R>>> = vec![Tape::<Stamped<R>>::new()];
+    let mut seq = 0;
+    while !target[0].is_empty() {
+        stamped[0].push(Stamped{ record: target[0].next_record(), seq });
+        seq+=1;
+    }
+    stamped[0].flush()?;
+    let mut disk_ops = target[0].disk_ops();
+    clear_tapes(target);
+
+    let mut info = strategy(&mut stamped)?;
+
+    let sorted_ops = stamped[0].disk_ops();
+    let mut writer = Writer::new(reduce, cmp);
+    while !stamped[0].is_empty() {
+        writer.push(stamped[0].next_record().record, &mut target[0]);
+    }
+    writer.finish(&mut target[0]);
+    target[0].run_len.set_single(writer.written);
+    target[0].flush()?;
+    disk_ops += stamped[0].disk_ops() - sorted_ops + target[0].disk_ops();
+
+    info.disk_ops += disk_ops;
+    info.eliminated += writer.eliminated;
+    return Ok(info);
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::triangle::Triangle;
+    use crate::natural_sort::{sort_stable, natural_order, cost::Algorithm, FORCE_EXTERNAL, PRINT_TAPES, DISPLAY_AFTER_RUN};
+
+    /* output is a stable sort of input when it matches an in-memory stable sort record by record */
+    fn is_stable_sort<R>(input: &[R], output: &[R], cmp: Compare<R>) -> bool
+    where R: Serialize+Copy {
+        let mut expected = input.to_vec();
+        expected.sort_by(cmp);
+        return expected.len() == output.len() && expected.iter().zip(output).all(|(a, b)| {
+            bincode::serialize(a).ok() == bincode::serialize(b).ok()
+        });
+    }
+
+    #[test]
+    fn every_algorithm_is_stable() {
+        *PRINT_TAPES.lock().unwrap() = false;
+        *DISPLAY_AFTER_RUN.lock().unwrap() = false;
+        /* skewed triangles are mostly tiny, so many distinct ones share an area */
+        let mut rng = StdRng::seed_from_u64(32);
+        let input: Vec<Triangle> = (0..2000).map(|_| Triangle::skewed(&mut rng)).collect();
+        for external in [false, true] {
+            *FORCE_EXTERNAL.lock().unwrap() = external;
+            /* polyphase merge has only a cost model */
+            for algorithm in [Algorithm::Natural, Algorithm::Balanced, Algorithm::Cascade, Algorithm::Oscillating,
+                              Algorithm::Planned, Algorithm::Distribution] {
+                let mut target: Vec<Tape<Triangle>> = vec![input.iter().copied().collect()];
+                let info = sort_stable(algorithm, &mut target, 3, &natural_order, &mut Reduce::Keep).unwrap();
+                assert_eq!(info.in_memory, !external, "{algorithm}");
+                let output = target[0].iter().collect::<Result<Vec<Triangle>, _>>().unwrap();
+                assert!(is_stable_sort(&input, &output, &natural_order), "{algorithm} external: {external}");
+            }
+        }
+        *FORCE_EXTERNAL.lock().unwrap() = false;
+    }
+}
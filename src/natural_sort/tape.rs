@@ -7,19 +7,25 @@ use std::{
     fs::{self},
     path::PathBuf,
     fmt::Display,
-    io
+    io,
+    mem::size_of
 };
 use rand::prelude::Distribution;
 use rand::distributions::Standard;
 use serde::{Serialize, Deserialize};
 
 use crate::file_handler::FileHandler;
+
+use super::{run_index::RunIndex, PRINT_TAPES};
 static TAPE_NUM: Mutex<usize> = Mutex::new(0);
 
+/* how many tapes (files) one sort may keep open at once */
+pub const MAX_OPEN_TAPES: usize = 64;
+
 pub struct Tape<R> 
 where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
     record_type: PhantomData<R>,
-    pub run_len: Vec<usize>,
+    pub run_len: RunIndex,
     file: FileHandler,
 }
 
@@ -30,13 +36,14 @@ where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distrib
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         let mut tape_num = TAPE_NUM.lock().unwrap(); 
         path.push(format!("data/t{tape_num}.tape"));
+        let run_len = RunIndex::new(path.with_extension("runs"));
 
         match FileHandler::open(path) {
             Ok(file) => {
                 *tape_num+=1;
                 return Self{
                     record_type: PhantomData,
-                    run_len:Vec::new(),
+                    run_len,
                     file, 
                 }
             }
@@ -63,21 +70,82 @@ where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distrib
         return self.file.eof();
     }
     pub fn print(&mut self) {
+        let print = *PRINT_TAPES.lock().unwrap();
+        if !print { return; }
         self.file.print_content::<R>().expect("TODO: panic message");
         print!(" n: ");
-        for run in &mut *self.run_len { print!("{run} "); }
+        for run in self.run_len.lens() { print!("{run} "); }
         println!("disk_ops: {}", self.disk_ops());
         println!();
     }
     pub fn flush(&mut self) -> Result<(), io::Error> {
         self.file.flush()
     }
+    /* back to the first record without writing anything, for tapes that were only read */
+    pub fn rewind(&mut self) -> Result<(), io::Error> {
+        self.file.rewind()
+    }
     pub fn clear(&mut self) -> Result<(), io::Error> {
         self.file.clear()?;
-        self.run_len = Vec::new();
+        self.run_len.clear();
+        self.run_len.reset_disk_ops();
         Ok(())
     }
-    pub fn disk_ops(&mut self) -> usize { return self.file.disk_ops(); }
+    /* pages of records and of the run index */
+    pub fn disk_ops(&mut self) -> usize { return self.file.disk_ops() + self.run_len.disk_ops(); }
+    /* number of records saved on disk, pending page is not counted */
+    pub fn record_count(&self) -> usize {
+        return self.file.file_len().map_or(0, |len| len as usize/size_of::<R>());
+    }
+
+    /* tape used as a stack of runs: the last run is read from its start and then cut off */
+    pub fn open_last_run(&mut self) -> Result<usize, io::Error> {
+        let len = match self.run_len.last() {
+            Some(len) => len,
+            None => return Ok(0)
+        };
+        let offset = self.run_offset(self.run_len.len() - 1);
+        self.file.seek(offset)?;
+        Ok(len)
+    }
+    pub fn pop_run(&mut self) -> Result<(), io::Error> {
+        if self.run_len.pop().is_none() { return Ok(()); }
+        let offset = self.run_offset(self.run_len.len());
+        self.file.truncate(offset)
+    }
+
+    pub fn seek_record(&mut self, index: usize) -> Result<(), io::Error> {
+        self.file.seek((index*size_of::<R>()) as u64)
+    }
+
+    /* separate reader of one run, the tape can be written to at the same time */
+    pub fn run_reader(&mut self, run: usize) -> Result<RunReader<R>, io::Error> {
+        return self.run_reader_at(run, 0);
+    }
+    /* run_reader that starts at the index-th record of the run */
+    pub fn run_reader_at(&mut self, run: usize, index: usize) -> Result<RunReader<R>, io::Error> {
+        self.file.sync()?;
+        let mut file = FileHandler::open_existing(self.file.path())?;
+        file.seek(self.run_offset(run) + (index*size_of::<R>()) as u64)?;
+        Ok(RunReader {
+            record_type: PhantomData,
+            file
+        })
+    }
+
+    /* records from where the tape is to its end, a record that cannot be read is an error instead of a panic */
+    pub fn iter(&mut self) -> Records<'_, R> {
+        return Records { tape: self };
+    }
+    /* every run of run_len in order, each one read by its own reader */
+    pub fn runs(&mut self) -> RunIter<'_, R> {
+        return RunIter { tape: self, run: 0 };
+    }
+
+    /* private methods */
+    fn run_offset(&mut self, run: usize) -> u64 {
+        return (self.run_len.offset(run)*size_of::<R>()) as u64;
+    }
 
 }
 
@@ -85,6 +153,144 @@ impl<R> Drop for Tape<R>
 where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
     fn drop(&mut self) {
         /* remove created tape */
-        let _ = fs::remove_file(&self.file.path());
+        let _ = fs::remove_file(self.file.path());
+    }
+}
+
+/* records are pushed onto the tape, like push */
+impl<R> Extend<R> for Tape<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    fn extend<I: IntoIterator<Item = R>>(&mut self, records: I) {
+        for record in records { self.push(record); }
+    }
+}
+/* new tape holding the records, flushed so it can be read right away */
+impl<R> FromIterator<R> for Tape<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    fn from_iter<I: IntoIterator<Item = R>>(records: I) -> Self {
+        let mut tape = Tape::new();
+        tape.extend(records);
+        tape.flush().expect("cannot flush tape");
+        return tape;
+    }
+}
+
+pub struct Records<'t, R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    tape: &'t mut Tape<R>
+}
+
+impl<R> Iterator for Records<'_, R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    type Item = io::Result<R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.tape.is_empty() { return None; }
+        return match self.tape.file.read::<R>() {
+            Ok(record) => Some(Ok(record)),
+            /* an empty tape only finds out it is empty by reading */
+            Err(_) if self.tape.is_empty() => None,
+            Err(err) => Some(Err(io::Error::new(io::ErrorKind::InvalidData, err)))
+        }
+    }
+}
+
+pub struct RunIter<'t, R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    tape: &'t mut Tape<R>,
+    run: usize
+}
+
+impl<R> Iterator for RunIter<'_, R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    type Item = io::Result<Run<R>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = self.tape.run_len.get(self.run)?;
+        let run = self.tape.run_reader(self.run).map(|reader| Run { reader, left: len });
+        self.run+=1;
+        return Some(run);
+    }
+}
+
+/* records of one run, the tape can be written to while they are read */
+pub struct Run<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    reader: RunReader<R>,
+    left: usize
+}
+
+impl<R> Run<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    pub fn disk_ops(&self) -> usize { return self.reader.disk_ops(); }
+}
+
+impl<R> Iterator for Run<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    type Item = io::Result<R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.left == 0 { return None; }
+        self.left-=1;
+        return Some(self.reader.file.read::<R>().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)));
     }
-}
\ No newline at end of file
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        return (self.left, Some(self.left));
+    }
+}
+impl<R> ExactSizeIterator for Run<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {}
+
+pub struct RunReader<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    record_type: PhantomData<R>,
+    file: FileHandler,
+}
+
+impl<R> RunReader<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    pub fn disk_ops(&self) -> usize { return self.file.disk_ops(); }
+}
+
+/* anything merge can read records from */
+pub trait RecordSource<R> {
+    fn next_record(&mut self) -> R;
+    fn view_record(&mut self) -> R;
+}
+
+impl<R> RecordSource<R> for Tape<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    fn next_record(&mut self) -> R { return Tape::next_record(self); }
+    fn view_record(&mut self) -> R { return Tape::view_record(self); }
+}
+
+impl<R> RecordSource<R> for RunReader<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    fn next_record(&mut self) -> R {
+        match self.file.read::<R>() {
+            Ok(record) => return record,
+            Err(_) => panic!("Problem deserializing record")
+        }
+    }
+    fn view_record(&mut self) -> R {
+        match self.file.view::<R>() {
+            Ok(record) => return record,
+            Err(_) => panic!("Problem deserializing record")
+        }
+    }
+}
+
+/* merges keep their own count of what is left, next_record still counts the record as read */
+impl<R> RecordSource<R> for Run<R>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display, Standard: Distribution<R> {
+    fn next_record(&mut self) -> R {
+        self.left = self.left.saturating_sub(1);
+        return self.reader.next_record();
+    }
+    fn view_record(&mut self) -> R { return self.reader.view_record(); }
+}
+
+impl<R, S: RecordSource<R>> RecordSource<R> for &mut S {
+    fn next_record(&mut self) -> R { return (**self).next_record(); }
+    fn view_record(&mut self) -> R { return (**self).view_record(); }
+}
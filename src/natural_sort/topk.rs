@@ -0,0 +1,163 @@
+use std::{
+    io::Error,
+    cmp::Ordering,
+    cell::Cell,
+    collections::BinaryHeap,
+    fmt::Display,
+    str::FromStr,
+    mem::size_of
+};
+use rand::distributions::{Distribution, Standard};
+use serde::{Serialize, Deserialize};
+
+use crate::file_handler::BLOCK_SIZE;
+
+use super::{tape::Tape, cost, Compare, SortInfo, DISPLAY_AFTER_RUN, merge_run_limit, clear_tapes, print_info};
+
+/* record ordered by cmp, so it can live in a BinaryHeap */
+struct ByCmp<'c, R> {
+    record: R,
+    cmp: Compare<'c, R>
+}
+
+impl<R> Ord for ByCmp<'_, R> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return (self.cmp)(&self.record, &other.record);
+    }
+}
+impl<R> PartialOrd for ByCmp<'_, R> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+impl<R> PartialEq for ByCmp<'_, R> {
+    fn eq(&self, other: &Self) -> bool {
+        return self.cmp(other) == Ordering::Equal;
+    }
+}
+impl<R> Eq for ByCmp<'_, R> {}
+
+/* leaves only the first k records of the sorted order on target[0],
+   budget is the number of records that may be kept in memory, n the fan-in of merges when k does not fit */
+pub fn top_k<R>(target: &mut [Tape<R>], k: usize, budget: usize, n: usize, cmp: Compare<R>) -> Result<SortInfo, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let comparisons = Cell::new(0);
+    let counted = |a: &R, b: &R| {
+        comparisons.set(comparisons.get() + 1);
+        cmp(a, b)
+    };
+    let records = target[0].record_count();
+    target[0].print();
+
+    let (number_of_phases, disk_ops, runs) = if k <= budget {
+        (1, heap_pass(target, k, &counted)?, 0)
+    } else {
+        spill(target, k, budget.max(1), n.max(2), &counted)?
+    };
+    println!();println!();
+    target[0].print();
+
+    let b: f32 = (BLOCK_SIZE/size_of::<R>()) as f32;
+    let teor = cost::top_k(n, k, budget, records, b);
+    return Ok(SortInfo {
+        number_of_phases,
+        disk_ops,
+
+        teor_number_of_phases: teor.phases,
+        teor_disk_ops: teor.disk_ops,
+
+        ascending_runs: runs,
+        descending_runs: 0,
+        buckets: 0,
+
+        comparisons: comparisons.get(),
+        prefix_hits: 0,
+        eliminated: 0,
+
+        in_memory: false
+    })
+}
+
+/* one pass, the heap root is the worst record kept so far */
+fn heap_pass<R>(target: &mut [Tape<R>], k: usize, cmp: Compare<R>) -> Result<usize, Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let mut heap = BinaryHeap::with_capacity(k);
+    while !target[0].is_empty() {
+        let record = target[0].next_record();
+        if heap.len() < k {
+            heap.push(ByCmp{ record, cmp });
+        } else if let Some(mut worst) = heap.peek_mut() {
+            if cmp(&record, &worst.record) == Ordering::Less {
+                worst.record = record;
+            }
+        }
+    }
+    let mut disk_ops = target[0].disk_ops();
+    clear_tapes(target);
+
+    let best = heap.into_sorted_vec();
+    for kept in &best {
+        target[0].push(kept.record);
+    }
+    target[0].run_len.push(best.len());
+    target[0].flush()?;
+    disk_ops+=target[0].disk_ops();
+    return Ok(disk_ops);
+}
+
+/* runs of budget records sorted in memory, merged n at a time and cut to k records,
+   returns (phases, disk ops, runs) */
+fn spill<R>(target: &mut [Tape<R>], k: usize, budget: usize, n: usize, cmp: Compare<R>) -> Result<(usize, usize, usize), Error>
+where R: Serialize+for<'a> Deserialize<'a>+PartialOrd+Display+Copy+FromStr, Standard: Distribution<R> {
+    let mut disk_ops = 0;
+    let mut spool: Vec<Tape<R>> = vec![Tape::new()];
+    let mut chunk = Vec::with_capacity(budget);
+    while !target[0].is_empty() {
+        chunk.push(target[0].next_record());
+        if chunk.len() == budget || target[0].is_empty() {
+            chunk.sort_by(cmp);
+            spool[0].run_len.push(chunk.len());
+            for record in chunk.drain(..) { spool[0].push(record); }
+        }
+    }
+    spool[0].flush()?;
+    let runs = spool[0].run_len.len();
+    for tape in target.iter_mut() { disk_ops+=tape.disk_ops() }
+    clear_tapes(target);
+
+    let display = DISPLAY_AFTER_RUN.lock().unwrap();
+    let mut number_of_phases = 1;
+    loop {
+        if *display {
+            print_info(&mut spool);
+            println!();println!();
+        }
+        /* the last merge goes straight onto the target tape */
+        let last = spool[0].run_len.len() <= n;
+        let mut merged: Vec<Tape<R>> = vec![Tape::new()];
+        let mut runs = spool[0].runs();
+        loop {
+            let mut inputs = Vec::new();
+            for run in runs.by_ref().take(n) {
+                let run = run?;
+                let len = run.len();
+                inputs.push((run, len));
+            }
+            if inputs.is_empty() { break; }
+            let output = if last { &mut target[0] } else { &mut merged[0] };
+            merge_run_limit(&mut inputs, output, cmp, k);
+            for (reader, _) in &inputs { disk_ops+=reader.disk_ops() }
+        }
+        number_of_phases+=1;
+        for tape in spool.iter_mut() { disk_ops+=tape.disk_ops() }
+        if last { break; }
+        merged[0].flush()?;
+        spool = merged;
+    }
+    target[0].flush()?;
+    for tape in target.iter_mut() { disk_ops+=tape.disk_ops() }
+    if *display {
+        print_info(target);
+    }
+    return Ok((number_of_phases, disk_ops, runs));
+}
@@ -0,0 +1,217 @@
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display},
+    str::FromStr
+};
+
+use crate::natural_sort::{prefix::{NormalizedKey, encode_f64, encode_i32, encode_u64}, radix::RadixKey};
+
+use super::Triangle;
+
+/* properties a triangle can be sorted by */
+#[derive(Clone, Copy, PartialEq)]
+pub enum Key {
+    Area,
+    Perimeter,
+    LongestSide,
+    CentroidX,
+    CentroidY,
+    MinVertex,
+    BoundingBoxArea
+}
+
+impl Key {
+    pub const ALL: [Key; 7] = [Key::Area, Key::Perimeter, Key::LongestSide, Key::CentroidX, Key::CentroidY, Key::MinVertex, Key::BoundingBoxArea];
+
+    pub fn value(self, triangle: &Triangle) -> KeyValue {
+        return match self {
+            Key::Area => KeyValue::Exact(triangle.doubled_area()),
+            Key::Perimeter => KeyValue::Float(triangle.perimeter()),
+            Key::LongestSide => KeyValue::Float(triangle.longest_side()),
+            Key::CentroidX => KeyValue::Float(triangle.centroid().0),
+            Key::CentroidY => KeyValue::Float(triangle.centroid().1),
+            Key::MinVertex => {
+                let vertex = triangle.min_vertex();
+                KeyValue::Point(vertex.x, vertex.y)
+            }
+            Key::BoundingBoxArea => KeyValue::Int(triangle.bounding_box_area())
+        }
+    }
+    pub fn compare(self, a: &Triangle, b: &Triangle) -> Ordering {
+        return self.value(a).partial_cmp(&self.value(b)).unwrap_or(Ordering::Equal);
+    }
+    /* width of the key as an unsigned number, None for keys that are not integers */
+    pub fn integer_bits(self) -> Option<u32> {
+        return match self {
+            /* twice the area of i32 coordinates is below 2^65 */
+            Key::Area => Some(66),
+            Key::MinVertex | Key::BoundingBoxArea => Some(64),
+            Key::Perimeter | Key::LongestSide | Key::CentroidX | Key::CentroidY => None
+        }
+    }
+}
+
+/* value of a key, values of the same key always have the same variant */
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum KeyValue {
+    Float(f64),
+    Point(i32, i32),
+    Int(u64),
+    Exact(u128)
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Key::Area => "area",
+            Key::Perimeter => "perimeter",
+            Key::LongestSide => "longest-side",
+            Key::CentroidX => "centroid-x",
+            Key::CentroidY => "centroid-y",
+            Key::MinVertex => "min-vertex",
+            Key::BoundingBoxArea => "bbox-area"
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match Key::ALL.iter().find(|key| key.to_string() == s) {
+            Some(&key) => Ok(key),
+            None => Err(format!("unknown key: {s} (area/perimeter/longest-side/centroid-x/centroid-y/min-vertex/bbox-area)"))
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct SortKey {
+    pub key: Key,
+    pub descending: bool
+}
+
+/* list of keys like "perimeter desc, area asc", later keys break ties of earlier ones */
+#[derive(Clone)]
+pub struct KeySpec(pub Vec<SortKey>);
+
+impl KeySpec {
+    /* the only key when it is ascending, such a spec can be sorted by its key value */
+    pub fn single(&self) -> Option<Key> {
+        return match self.0.as_slice() {
+            [key] if !key.descending => Some(key.key),
+            _ => None
+        }
+    }
+    pub fn compare(&self, a: &Triangle, b: &Triangle) -> Ordering {
+        for key in &self.0 {
+            let ordering = key.key.compare(a, b);
+            let ordering = if key.descending { ordering.reverse() } else { ordering };
+            if ordering != Ordering::Equal { return ordering; }
+        }
+        return Ordering::Equal;
+    }
+}
+
+/* keys one after another, descending keys have their bytes inverted */
+impl NormalizedKey<Triangle> for KeySpec {
+    fn key_len(&self) -> usize {
+        return 8*self.0.len();
+    }
+    /* doubled areas above 2^53 share their f64 encoding */
+    fn is_exact(&self) -> bool {
+        return self.0.iter().all(|key| key.key != Key::Area);
+    }
+    fn encode(&self, triangle: &Triangle, out: &mut Vec<u8>) {
+        for key in &self.0 {
+            let start = out.len();
+            match key.key.value(triangle) {
+                KeyValue::Float(value) => encode_f64(value, out),
+                KeyValue::Point(x, y) => {
+                    encode_i32(x, out);
+                    encode_i32(y, out);
+                }
+                KeyValue::Int(value) => encode_u64(value, out),
+                /* rounding keeps the order, equal prefixes are settled by the exact value */
+                KeyValue::Exact(value) => encode_f64(value as f64, out)
+            }
+            if key.descending {
+                for byte in &mut out[start..] { *byte = !*byte; }
+            }
+        }
+    }
+}
+
+/* a KeySpec of integer keys only, the keys one after another make one number */
+pub struct IntegerKeys(Vec<SortKey>);
+
+impl TryFrom<&KeySpec> for IntegerKeys {
+    type Error = String;
+
+    fn try_from(spec: &KeySpec) -> Result<Self, Self::Error> {
+        let mut bits = 0;
+        for key in &spec.0 {
+            match key.key.integer_bits() {
+                Some(key_bits) => bits+=key_bits,
+                None => return Err(format!("{} is not an integer key (area/min-vertex/bbox-area)", key.key))
+            }
+        }
+        if bits > u128::BITS {
+            return Err(format!("keys {spec} need {bits} bits, at most {} fit", u128::BITS));
+        }
+        return Ok(IntegerKeys(spec.0.clone()));
+    }
+}
+
+/* signed numbers have the sign bit flipped and descending keys every bit, like the normalized key */
+impl RadixKey<Triangle> for IntegerKeys {
+    fn key_bits(&self) -> u32 {
+        return self.0.iter().filter_map(|key| key.key.integer_bits()).sum();
+    }
+    fn key(&self, triangle: &Triangle) -> u128 {
+        let mut number = 0;
+        for key in &self.0 {
+            let bits = key.key.integer_bits().unwrap_or(0);
+            let value = match key.key.value(triangle) {
+                KeyValue::Exact(value) => value,
+                KeyValue::Int(value) => value as u128,
+                KeyValue::Point(x, y) => ((((x as u32) ^ (1 << 31)) as u128) << 32) | ((y as u32) ^ (1 << 31)) as u128,
+                KeyValue::Float(_) => 0
+            };
+            let mask = (1_u128 << bits) - 1;
+            number = (number << bits) | if key.descending { !value & mask } else { value };
+        }
+        return number;
+    }
+}
+
+impl Display for KeySpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys: Vec<String> = self.0.iter()
+            .map(|key| format!("{} {}", key.key, if key.descending { "desc" } else { "asc" }))
+            .collect();
+        write!(f, "{}", keys.join(", "))
+    }
+}
+
+impl FromStr for KeySpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys = Vec::new();
+        for part in s.split(',') {
+            let words: Vec<&str> = part.split_whitespace().collect();
+            let descending = match words.get(1) {
+                None | Some(&"asc") => false,
+                Some(&"desc") => true,
+                Some(word) => return Err(format!("expected asc or desc, got {word}"))
+            };
+            match words.first() {
+                Some(key) if words.len() <= 2 => keys.push(SortKey{ key: key.parse()?, descending }),
+                _ => return Err(format!("wrong sort key: {}", part.trim()))
+            }
+        }
+        return Ok(KeySpec(keys));
+    }
+}
@@ -2,10 +2,13 @@ use point::Point;
 use rand::{prelude::Distribution, distributions::Standard};
 use serde_derive::{Serialize, Deserialize};
 use std::num::ParseIntError;
-use std::{fmt, str};
+use std::{fmt, str, io::{self, Write}};
 use std::cmp::Ordering;
 
+use crate::natural_sort::presorted::WriteRecord;
+
 mod point;
+pub mod key;
 
 #[derive(Copy, Clone, Serialize, Deserialize, Eq)]
 pub struct Triangle {
@@ -15,18 +18,50 @@ pub struct Triangle {
 }
 
 impl Triangle {
+    /* twice the area from the shoelace formula, exact for any coordinates */
+    pub fn doubled_area(self) -> u128 {
+        let (ax, ay) = (self.a.x as i128, self.a.y as i128);
+        let cross = (self.b.x as i128 - ax)*(self.c.y as i128 - ay) - (self.c.x as i128 - ax)*(self.b.y as i128 - ay);
+        return cross.unsigned_abs();
+    }
+    /* only for display, ordering and equality use doubled_area */
     pub fn tri_area(self) -> f64 {
-        // Heron's formula
-        let a: f64 = self.a.distance(self.b);
-        let b: f64 = self.b.distance(self.c);
-        let c: f64 = self.c.distance(self.a);
-        let s: f64 = (a+b+c)*0.5;
-        
-        let area = (s*(s-a)*(s-b)*(s-c)).sqrt();
-        return if area.is_nan() {0.0} else {area};
+        return self.doubled_area() as f64*0.5;
+    }
+
+    pub fn perimeter(self) -> f64 {
+        return self.a.distance(self.b) + self.b.distance(self.c) + self.c.distance(self.a);
+    }
+    pub fn longest_side(self) -> f64 {
+        return self.a.distance(self.b).max(self.b.distance(self.c)).max(self.c.distance(self.a));
+    }
+    pub fn centroid(self) -> (f64, f64) {
+        let x = self.a.x as f64 + self.b.x as f64 + self.c.x as f64;
+        let y = self.a.y as f64 + self.b.y as f64 + self.c.y as f64;
+        return (x/3.0, y/3.0);
+    }
+    /* lowest x, then lowest y */
+    pub fn min_vertex(self) -> Point {
+        return [self.a, self.b, self.c].into_iter().min_by_key(|p| (p.x, p.y)).unwrap();
+    }
+    /* both sides are below 2^32, so the area always fits */
+    pub fn bounding_box_area(self) -> u64 {
+        let xs = [self.a.x, self.b.x, self.c.x];
+        let ys = [self.a.y, self.b.y, self.c.y];
+        let width = xs.iter().max().unwrap().abs_diff(*xs.iter().min().unwrap()) as u64;
+        let height = ys.iter().max().unwrap().abs_diff(*ys.iter().min().unwrap()) as u64;
+        return width*height;
+    }
+    pub fn vertices(self) -> [(i32, i32); 3] {
+        return [(self.a.x, self.a.y), (self.b.x, self.b.y), (self.c.x, self.c.y)];
     }
 }
 
+/* area first, ties by the vertices in order: equal only for the same three vertices */
+pub fn vertex_order(a: &Triangle, b: &Triangle) -> Ordering {
+    return a.cmp(b).then_with(|| a.vertices().cmp(&b.vertices()));
+}
+
 impl fmt::Display for Triangle {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{{ {} {} {} }} trig:{:.2}", self.a, self.b, self.c, self.tri_area())
@@ -66,24 +101,40 @@ impl str::FromStr for Triangle {
     }
 }
 
+/* {(x,y),(x,y),(x,y)}, the form from_str reads */
+impl WriteRecord for Triangle {
+    fn write_record(&self, out: &mut dyn Write) -> io::Result<()> {
+        let [(ax, ay), (bx, by), (cx, cy)] = self.vertices();
+        write!(out, "{{({ax},{ay}),({bx},{by}),({cx},{cy})}}")
+    }
+}
+
 // impl cmp
 impl Ord for Triangle {
     fn cmp(&self, other: &Self) -> Ordering {
-        return self.partial_cmp(other).unwrap();
+        return self.doubled_area().cmp(&other.doubled_area());
     }
 }
 impl PartialOrd for Triangle {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        return self.tri_area().partial_cmp(&other.tri_area());    
+        return Some(self.cmp(other));
     }
 }
 impl PartialEq for Triangle {
     fn eq(&self, other: &Self) -> bool {
-        return self.tri_area() == other.tri_area();
+        return self.doubled_area() == other.doubled_area();
     }
 }
 
 /* random */
+impl Triangle {
+    /* vertices close to each other, most triangles are tiny and many have the same area */
+    pub fn skewed<R: rand::Rng + ?Sized>(rng: &mut R) -> Triangle {
+        let spread = 1 << rng.gen_range(0..6);
+        let mut vertex = || Point{x: rng.gen_range(-spread..=spread), y: rng.gen_range(-spread..=spread)};
+        return Triangle{a: vertex(), b: vertex(), c: vertex()};
+    }
+}
 impl Distribution<Triangle> for Standard {
     fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Triangle {
         return Triangle{a: rng.gen(), b: rng.gen(), c: rng.gen()};